@@ -1,6 +1,6 @@
 use std::cell::UnsafeCell;
 use std::error::Error;
-use std::fmt::{Display, Error as FormatError, Formatter};
+use std::fmt::{Debug, Display, Error as FormatError, Formatter};
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -66,6 +66,24 @@ impl<'a, T> Drop for RefMut<'a, T> {
     }
 }
 
+/// The way a `TrustCell` is currently borrowed.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum BorrowState {
+    Free,
+    Read(usize),
+    Write,
+}
+
+impl Debug for BorrowState {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FormatError> {
+        match *self {
+            BorrowState::Free => write!(f, "free"),
+            BorrowState::Read(n) => write!(f, "Read x{}", n),
+            BorrowState::Write => write!(f, "Write"),
+        }
+    }
+}
+
 /// A custom cell similar to
 /// `RefCell`, but it is thread-safe.
 #[derive(Debug)]
@@ -100,6 +118,37 @@ impl<T> TrustCell<T> {
         }
     }
 
+    /// Returns a reference to the inner value without checking
+    /// the borrow flag.
+    ///
+    /// # Safety
+    ///
+    /// The value must not be borrowed mutably while
+    /// the returned reference is alive.
+    pub unsafe fn get_unchecked(&self) -> &T {
+        &*self.inner.get()
+    }
+
+    /// Returns a mutable reference to the inner value. No check is
+    /// necessary, since the cell is borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.inner.get() }
+    }
+
+    /// Consumes the cell, returning the inner value.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Returns how this cell is borrowed at the moment.
+    pub fn borrow_state(&self) -> BorrowState {
+        match self.flag.load(Ordering::Acquire) {
+            0 => BorrowState::Free,
+            val if val == !0 => BorrowState::Write,
+            val => BorrowState::Read(val),
+        }
+    }
+
     fn check_flag_read(&self) -> Result<(), InvalidBorrow> {
         loop {
             let val = self.flag.load(Ordering::Acquire);
@@ -155,6 +204,21 @@ mod tests {
         assert_eq!(10, *cell.borrow());
     }
 
+    #[test]
+    fn borrow_state() {
+        let cell: TrustCell<_> = TrustCell::new(5);
+        assert!(cell.borrow_state() == BorrowState::Free);
+
+        {
+            let _a = cell.borrow();
+            let _b = cell.borrow();
+            assert!(cell.borrow_state() == BorrowState::Read(2));
+        }
+
+        let _a = cell.borrow_mut();
+        assert!(cell.borrow_state() == BorrowState::Write);
+    }
+
     #[cfg(debug_assertions)]
     #[test]
     #[should_panic(expected = "Already borrowed mutably")]
@@ -0,0 +1,75 @@
+use dispatch::dependencies::Dependencies;
+
+/// An estimate of how well a dispatcher's systems can run in parallel,
+/// returned by [`DispatcherBuilder::benchmark_graph`].
+///
+/// The estimate is based on the dependencies and the given cost of each
+/// system only; resource conflicts and the number of threads are not
+/// taken into account.
+///
+/// [`DispatcherBuilder::benchmark_graph`]: struct.DispatcherBuilder.html#method.benchmark_graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkReport {
+    /// The cost of running all systems one after another.
+    pub sequential_us: u64,
+    /// The cost of the most expensive chain of dependent systems.
+    pub critical_path_us: u64,
+    /// `sequential_us / critical_path_us`, the best possible speedup
+    /// of a parallel dispatch over a sequential one.
+    pub theoretical_speedup: f64,
+    /// The names of the systems on the critical path,
+    /// in the order they have to run in.
+    pub bottleneck_systems: Vec<String>,
+}
+
+/// Computes the report for `deps`, where
+/// `cost(id)` is the cost of the system `id`.
+pub fn benchmark<F>(deps: &Dependencies, cost: F) -> BenchmarkReport
+where
+    F: Fn(usize) -> u64,
+{
+    let num = deps.len();
+    // The cost of the longest chain ending with each system
+    // and the previous system of that chain.
+    let mut finish = vec![0; num];
+    let mut prev = vec![None; num];
+
+    for id in deps.levels().into_iter().flatten() {
+        let longest = deps.dependencies_of(id)
+            .iter()
+            .map(|dep| dep.0)
+            .max_by_key(|&dep| (finish[dep], num - dep));
+
+        if let Some(dep) = longest {
+            finish[id] = finish[dep];
+            prev[id] = Some(dep);
+        }
+
+        finish[id] += cost(id);
+    }
+
+    let sequential_us = (0..num).map(&cost).sum();
+    let last = (0..num).max_by_key(|&id| (finish[id], num - id));
+    let critical_path_us = last.map(|id| finish[id]).unwrap_or(0);
+
+    let mut bottleneck_systems = Vec::new();
+    let mut current = last;
+    while let Some(id) = current {
+        bottleneck_systems.push(deps.name(id).to_owned());
+        current = prev[id];
+    }
+    bottleneck_systems.reverse();
+
+    let theoretical_speedup = if critical_path_us == 0 {
+        1.0
+    } else {
+        sequential_us as f64 / critical_path_us as f64
+    };
+
+    BenchmarkReport {
+        sequential_us,
+        critical_path_us,
+        theoretical_speedup,
+        bottleneck_systems,
+    }
+}
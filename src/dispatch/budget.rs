@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+/// Limits how much work a single call to
+/// [`Dispatcher::dispatch_budgeted`] may do.
+///
+/// [`Dispatcher::dispatch_budgeted`]: struct.Dispatcher.html#method.dispatch_budgeted
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExecutionBudget {
+    /// No limit; all systems are run.
+    Unlimited,
+    /// No new systems are started once the
+    /// dispatch took longer than this.
+    TimeLimit(Duration),
+}
+
+/// The result of [`Dispatcher::dispatch_budgeted`].
+///
+/// [`Dispatcher::dispatch_budgeted`]: struct.Dispatcher.html#method.dispatch_budgeted
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DispatchOutcome {
+    /// All remaining systems were run.
+    Completed {
+        /// The number of systems which were run by this call,
+        /// including thread local systems.
+        ran: usize,
+    },
+    /// The budget was exceeded before all systems could be run.
+    BudgetExceeded {
+        /// The ids of the systems which were run by this call.
+        completed: Vec<usize>,
+        /// The ids of the systems which will be run by the next call.
+        pending: Vec<usize>,
+    },
+}
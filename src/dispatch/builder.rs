@@ -1,9 +1,20 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::iter::FromIterator;
+
 use fnv::FnvHashMap;
+use smallvec::SmallVec;
 
 use dispatch::Dispatcher;
+use dispatch::benchmark::{benchmark, BenchmarkReport};
+use dispatch::budget::ExecutionBudget;
+use dispatch::dependencies::Dependencies;
 use dispatch::dispatcher::{SystemId, ThreadLocal};
-use dispatch::stage::StagesBuilder;
-use system::System;
+use dispatch::error::{BuildError, ErrorStrategy};
+use dispatch::stage::{Stage, StagesBuilder, SystemEntry};
+use res::{Resource, ResourceId, Resources};
+use system::{RunNow, RunningTime, System};
 
 /// Builder for the [`Dispatcher`].
 ///
@@ -84,15 +95,47 @@ use system::System;
 /// # }
 /// ```
 ///
+/// ## Errors
+///
+/// By default, invalid registrations (like unknown dependencies)
+/// make the builder panic immediately. Using `ErrorStrategy::Collect`,
+/// all errors are collected and returned from `try_build()` instead.
+///
+/// The `Debug` implementation lists all the registered systems
+/// in the order they were added, together with their dependencies
+/// and the resources they read and write.
+///
 #[derive(Default)]
 pub struct DispatcherBuilder<'a, 'b> {
     current_id: usize,
+    dependencies: Dependencies,
     map: FnvHashMap<String, SystemId>,
-    stages_builder: StagesBuilder<'a>,
+    systems: Vec<SystemEntry<'a>>,
+    barriers: Vec<usize>,
+    declared: Vec<String>,
+    labels: FnvHashMap<String, Vec<SystemId>>,
+    terminal: Vec<SystemId>,
+    allowed_rewrites: Vec<(SystemId, ResourceId)>,
+    allowed_no_resources: Vec<SystemId>,
+    /// Dependencies on declared systems which weren't added yet,
+    /// as (depending system, name of the dependency).
+    pending: Vec<(SystemId, String)>,
+    collect_errors: bool,
+    errors: Vec<BuildError>,
     thread_local: ThreadLocal<'b>,
+    startup: ThreadLocal<'b>,
+    shutdown: ThreadLocal<'b>,
+    shutdown_names: Vec<String>,
+    time_limit: Option<::std::time::Duration>,
     #[cfg(not(target_os = "emscripten"))]
     thread_pool:
         Option<::std::sync::Arc<::rayon::ThreadPool>>,
+    #[cfg(not(target_os = "emscripten"))]
+    no_default_pool: bool,
+    #[cfg(not(target_os = "emscripten"))]
+    pool_name: Option<String>,
+    #[cfg(not(target_os = "emscripten"))]
+    max_concurrent: Option<usize>,
 }
 
 impl<'a, 'b> DispatcherBuilder<'a, 'b> {
@@ -105,9 +148,21 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
         Default::default()
     }
 
+    /// Creates a new `DispatcherBuilder` with space
+    /// for `systems` systems, so adding them doesn't
+    /// need to reallocate.
+    pub fn with_capacity(systems: usize) -> Self {
+        let mut builder = Self::new();
+        builder.dependencies = Dependencies::with_capacity(systems);
+        builder.map.reserve(systems);
+        builder.systems.reserve(systems);
+
+        builder
+    }
+
     /// Adds a new system with a given name and a list of dependencies.
     /// Please note that the dependency should be added before
-    /// you add the depending system.
+    /// you add the depending system (or be declared with `declare`).
     ///
     /// If you want to register systems which can not be specified as
     /// dependencies, you can use `""` as their name, which will not panic
@@ -120,6 +175,7 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     ///
     /// * if the specified dependency does not exist
     /// * if a system with the same name was already registered.
+    #[track_caller]
     pub fn with<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
     where
         T: for<'c> System<'c> + Send + 'a,
@@ -131,44 +187,196 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
 
     /// Adds a new system with a given name and a list of dependencies.
     /// Please note that the dependency should be added before
-    /// you add the depending system.
+    /// you add the depending system (or be declared with `declare`).
     ///
     /// If you want to register systems which can not be specified as
     /// dependencies, you can use `""` as their name, which will not panic
     /// (using another name twice will).
     ///
+    /// The location `add` is called from is remembered; if the system
+    /// panics during `Dispatcher::try_dispatch`, the error points to it.
+    ///
+    /// The system has to be `Send`, because it may be run on
+    /// any thread of the thread pool. Systems which aren't `Send`
+    /// can be added with `add_thread_local` instead:
+    ///
+    /// ```compile_fail
+    /// # use std::rc::Rc;
+    /// # use shred::{DispatcherBuilder, System};
+    /// struct NotSend(Rc<u32>);
+    ///
+    /// impl<'a> System<'a> for NotSend {
+    ///     type SystemData = ();
+    ///
+    ///     fn run(&mut self, _: ()) {}
+    /// }
+    ///
+    /// let mut builder = DispatcherBuilder::new();
+    /// builder.add(NotSend(Rc::new(5)), "not_send", &[]);
+    /// ```
+    ///
+    /// ```
+    /// # use std::rc::Rc;
+    /// # use shred::{DispatcherBuilder, System};
+    /// # struct NotSend(Rc<u32>);
+    /// #
+    /// # impl<'a> System<'a> for NotSend {
+    /// #     type SystemData = ();
+    /// #
+    /// #     fn run(&mut self, _: ()) {}
+    /// # }
+    /// #
+    /// let mut builder = DispatcherBuilder::new();
+    /// builder.add_thread_local(NotSend(Rc::new(5)));
+    /// ```
+    ///
     /// # Panics
     ///
     /// * if the specified dependency does not exist
     /// * if a system with the same name was already registered.
+    #[track_caller]
     pub fn add<T>(&mut self, system: T, name: &str, dep: &[&str])
     where
         T: for<'c> System<'c> + Send + 'a,
     {
-        use std::collections::hash_map::Entry;
+        use std::panic::Location;
 
-        let id = self.next_id();
+        let (id, dependencies) = self.register(name, dep);
+        let entry = SystemEntry::new(system, id, name, dependencies, Location::caller());
 
-        let dependencies = dep.iter()
-            .map(|x| {
-                *self.map
-                    .get(*x)
-                    .expect(&format!("No such system registered (\"{}\")", *x))
-            })
-            .collect();
+        self.insert(entry);
+    }
 
-        if name != "" {
-            if let Entry::Vacant(e) = self.map.entry(name.to_owned()) {
-                e.insert(id);
-            } else {
-                panic!(
-                    "Cannot insert multiple systems with the same name (\"{}\")",
-                    name
-                );
-            }
+    /// Adds several systems of the same type, given as
+    /// `(system, name, dependencies)` tuples, in order.
+    ///
+    /// This is useful when the systems are created from data, e.g.
+    /// a configuration file. Systems of different types have to be
+    /// added one by one.
+    ///
+    /// Same as [`add_many()`](struct.DispatcherBuilder.html#method.add_many), but
+    /// returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Same as `with`.
+    #[track_caller]
+    pub fn with_many<T, I>(mut self, systems: I) -> Self
+    where
+        T: for<'c> System<'c> + Send + 'a,
+        I: IntoIterator<Item = (T, String, Vec<String>)>,
+    {
+        self.add_many(systems);
+
+        self
+    }
+
+    /// Adds several systems of the same type.
+    ///
+    /// See [`with_many()`](struct.DispatcherBuilder.html#method.with_many)
+    /// for details.
+    #[track_caller]
+    pub fn add_many<T, I>(&mut self, systems: I)
+    where
+        T: for<'c> System<'c> + Send + 'a,
+        I: IntoIterator<Item = (T, String, Vec<String>)>,
+    {
+        for (system, name, dep) in systems {
+            let dep: Vec<_> = dep.iter().map(String::as_str).collect();
+            self.add(system, &name, &dep);
         }
+    }
+
+    /// Adds a new system, which is given by a closure
+    /// and a list of the resources it reads and writes.
+    ///
+    /// This is useful for small one-off systems, which would be
+    /// too verbose to write as a `System` implementation. The closure
+    /// fetches its resources itself from the `Resources` passed to it;
+    /// this bypasses the type safety of `SystemData`: you have to make sure
+    /// to only fetch the resources you declared in `reads` / `writes`,
+    /// otherwise fetching may panic.
+    ///
+    /// Same as
+    /// [`add_from_closure()`](struct.DispatcherBuilder.html#method.add_from_closure),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if the specified dependency does not exist
+    /// * if a system with the same name was already registered.
+    #[track_caller]
+    pub fn with_from_closure<F>(
+        mut self,
+        reads: Vec<ResourceId>,
+        writes: Vec<ResourceId>,
+        f: F,
+        name: &str,
+        dep: &[&str],
+    ) -> Self
+    where
+        F: FnMut(&Resources) + Send + 'a,
+    {
+        self.add_from_closure(reads, writes, f, name, dep);
+
+        self
+    }
+
+    /// Adds a new system, which is given by a closure
+    /// and a list of the resources it reads and writes.
+    ///
+    /// See [`with_from_closure()`](struct.DispatcherBuilder.html#method.with_from_closure)
+    /// for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use shred::{DispatcherBuilder, ResourceId, Resources};
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(5u32);
+    ///
+    /// let mut builder = DispatcherBuilder::new();
+    /// builder.add_from_closure(
+    ///     vec![ResourceId::new::<u32>()],
+    ///     vec![],
+    ///     |res: &Resources| println!("{}", *res.fetch::<u32>(0)),
+    ///     "print",
+    ///     &[],
+    /// );
+    ///
+    /// builder.build().dispatch(&res);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// * if the specified dependency does not exist
+    /// * if a system with the same name was already registered.
+    #[track_caller]
+    pub fn add_from_closure<F>(
+        &mut self,
+        reads: Vec<ResourceId>,
+        writes: Vec<ResourceId>,
+        f: F,
+        name: &str,
+        dep: &[&str],
+    ) where
+        F: FnMut(&Resources) + Send + 'a,
+    {
+        use std::panic::Location;
 
-        self.stages_builder.insert(dependencies, id, system);
+        let (id, dependencies) = self.register(name, dep);
+
+        self.insert(SystemEntry {
+            id,
+            name: name.to_owned(),
+            dependencies,
+            reads,
+            writes,
+            running_time: RunningTime::Average,
+            location: Location::caller(),
+            system: Box::new(ClosureSystem(f)),
+        });
     }
 
     /// Adds a new thread local system.
@@ -201,6 +409,87 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
         self.thread_local.push(Box::new(system));
     }
 
+    /// Adds a system which is only run once, on the first dispatch,
+    /// before any other system. This is useful for initialization,
+    /// like seeding random number generators.
+    ///
+    /// Startup systems are run in the order they were added in,
+    /// on the thread calling `dispatch`; after running, they're dropped.
+    ///
+    /// Same as
+    /// [`add_startup()`](struct.DispatcherBuilder.html#method.add_startup),
+    /// but returns `self` to enable method chaining.
+    pub fn with_startup<T>(mut self, system: T) -> Self
+    where
+        T: for<'c> System<'c> + 'b,
+    {
+        self.add_startup(system);
+
+        self
+    }
+
+    /// Adds a system which is only run once, on the first dispatch.
+    ///
+    /// See [`with_startup()`](struct.DispatcherBuilder.html#method.with_startup)
+    /// for details.
+    pub fn add_startup<T>(&mut self, system: T)
+    where
+        T: for<'c> System<'c> + 'b,
+    {
+        self.startup.push(Box::new(system));
+    }
+
+    /// Adds a system which is not run by `dispatch`,
+    /// but only by `Dispatcher::shutdown`, e.g. to save state.
+    ///
+    /// Names and dependencies of shutdown systems are separate
+    /// from the ones of the other systems; like with `add`, a
+    /// dependency has to be added before the depending system.
+    /// Shutdown systems are run one after another, on the thread
+    /// calling `shutdown`.
+    ///
+    /// Same as
+    /// [`add_shutdown()`](struct.DispatcherBuilder.html#method.add_shutdown),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if the specified dependency does not exist
+    /// * if a shutdown system with the same name was already registered.
+    pub fn with_shutdown<T>(mut self, system: T, name: &str, dep: &[&str]) -> Self
+    where
+        T: for<'c> System<'c> + 'b,
+    {
+        self.add_shutdown(system, name, dep);
+
+        self
+    }
+
+    /// Adds a system which is only run by `Dispatcher::shutdown`.
+    ///
+    /// See [`with_shutdown()`](struct.DispatcherBuilder.html#method.with_shutdown)
+    /// for details.
+    pub fn add_shutdown<T>(&mut self, system: T, name: &str, dep: &[&str])
+    where
+        T: for<'c> System<'c> + 'b,
+    {
+        for dep in dep {
+            if !self.shutdown_names.iter().any(|n| n == dep) {
+                self.report(BuildError::UnknownSystem((*dep).to_owned()));
+            }
+        }
+
+        if !name.is_empty() {
+            if self.shutdown_names.iter().any(|n| n == name) {
+                self.report(BuildError::DuplicateName(name.to_owned()));
+            } else {
+                self.shutdown_names.push(name.to_owned());
+            }
+        }
+
+        self.shutdown.push(Box::new(system));
+    }
+
     /// Inserts a barrier which assures that all systems
     /// added before the barrier are executed before the ones
     /// after this barrier.
@@ -230,82 +519,1143 @@ impl<'a, 'b> DispatcherBuilder<'a, 'b> {
     /// Thread-local systems are not affected by barriers;
     /// they're always executed at the end.
     pub fn add_barrier(&mut self) {
-        self.stages_builder.add_barrier();
+        self.barriers.push(self.systems.len());
     }
 
-    /// Attach a rayon thread pool to the builder
-    /// and use that instead of creating one.
+    /// Tags the system named `system` with `label`, so it can be
+    /// referred to together with other systems sharing the label
+    /// (see `with_dependency_on_label`).
     ///
     /// Same as
-    /// [`add_pool()`](struct.DispatcherBuilder.html#method.add_pool),
+    /// [`add_label()`](struct.DispatcherBuilder.html#method.add_label),
     /// but returns `self` to enable method chaining.
-    #[cfg(not(target_os = "emscripten"))]
-    pub fn with_pool(mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) -> Self {
-        self.add_pool(pool);
-        
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system does not exist.
+    pub fn with_label(mut self, system: &str, label: &str) -> Self {
+        self.add_label(system, label);
+
         self
     }
 
-    /// Attach a rayon thread pool to the builder
-    /// and use that instead of creating one.
-    #[cfg(not(target_os = "emscripten"))]
-    pub fn add_pool(&mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) {
-        self.thread_pool = Some(pool);
+    /// Tags the system named `system` with `label`.
+    ///
+    /// See [`with_label()`](struct.DispatcherBuilder.html#method.with_label)
+    /// for details.
+    pub fn add_label(&mut self, system: &str, label: &str) {
+        match self.try_lookup(system) {
+            Ok(id) => {
+                let ids = self.labels.entry(label.to_owned()).or_default();
+
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+            Err(e) => self.report(e),
+        }
     }
 
-    /// Builds the `Dispatcher`.
+    /// Removes `label` from the system named `system`; does nothing
+    /// if the system isn't tagged with the label.
     ///
-    /// In the future, this method will
-    /// precompute useful information in
-    /// order to speed up dispatching.
-    pub fn build(self) -> Dispatcher<'a, 'b> {
-        use dispatch::dispatcher::new_dispatcher;
+    /// Dependencies which were already added with `add_dependency_on_label`
+    /// are not affected.
+    ///
+    /// Same as
+    /// [`remove_label()`](struct.DispatcherBuilder.html#method.remove_label),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system does not exist.
+    pub fn without_label(mut self, system: &str, label: &str) -> Self {
+        self.remove_label(system, label);
 
-        #[cfg(not(target_os = "emscripten"))]
-        let d = new_dispatcher(
-            self.stages_builder.build(),
-            self.thread_local,
-            self.thread_pool.unwrap_or_else(Self::create_thread_pool),
-        );
+        self
+    }
 
-        #[cfg(target_os = "emscripten")]
-        let d = new_dispatcher(self.stages_builder.build(), self.thread_local);
+    /// Removes `label` from the system named `system`.
+    ///
+    /// See [`without_label()`](struct.DispatcherBuilder.html#method.without_label)
+    /// for details.
+    pub fn remove_label(&mut self, system: &str, label: &str) {
+        let id = match self.try_lookup(system) {
+            Ok(id) => id,
+            Err(e) => return self.report(e),
+        };
+
+        let now_empty = match self.labels.get_mut(label) {
+            Some(ids) => {
+                ids.retain(|&other| other != id);
+
+                ids.is_empty()
+            }
+            None => false,
+        };
 
-        d
+        if now_empty {
+            self.labels.remove(label);
+        }
     }
 
-    fn next_id(&mut self) -> SystemId {
-        let id = self.current_id;
-        self.current_id += 1;
+    /// Returns the names of all systems tagged with
+    /// `label`, in the order they were labelled in.
+    pub fn systems_with_label(&self, label: &str) -> Vec<&str> {
+        self.labels
+            .get(label)
+            .map(|ids| ids.iter().map(|id| self.dependencies.name(id.0)).collect())
+            .unwrap_or_default()
+    }
 
-        SystemId(id)
+    /// Adds a new system which depends on all systems tagged
+    /// with `label` so far, in addition to the ones listed in `dep`.
+    ///
+    /// Same as
+    /// [`add_after_label()`](struct.DispatcherBuilder.html#method.add_after_label),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Same as `with`.
+    #[track_caller]
+    pub fn with_after_label<T>(mut self, system: T, name: &str, label: &str, dep: &[&str]) -> Self
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.add_after_label(system, name, label, dep);
+
+        self
     }
 
-    #[cfg(not(target_os = "emscripten"))]
-    fn create_thread_pool() -> ::std::sync::Arc<::rayon::ThreadPool> {
-        use std::sync::Arc;
-        use rayon::{Configuration, ThreadPool};
+    /// Adds a new system which depends on all systems tagged with `label`.
+    ///
+    /// See [`with_after_label()`](struct.DispatcherBuilder.html#method.with_after_label)
+    /// for details.
+    #[track_caller]
+    pub fn add_after_label<T>(&mut self, system: T, name: &str, label: &str, dep: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.add(system, name, dep);
 
-        Arc::new(
-            ThreadPool::new(Configuration::new()).expect("Invalid thread pool configuration"),
-        )
+        let to = SystemId(self.current_id - 1);
+        if let Some(ids) = self.labels.get(label) {
+            for &from in ids {
+                self.dependencies.add_dependency(from, to);
+            }
+        }
     }
-}
 
-#[cfg(not(target_os = "emscripten"))]
-impl<'b> DispatcherBuilder<'static, 'b> {
-    /// Builds an async dispatcher.
+    /// Makes the system named `system` depend on all systems
+    /// which are tagged with `label` at the time of this call
+    /// (except for the system itself).
     ///
-    /// It does not allow non-static types and
-    /// accepts a `Resource` struct.
-    pub fn build_async<R>(self, res: R) -> ::dispatch::async::AsyncDispatcher<'b, R> {
-        use dispatch::async::new_async;
+    /// Same as
+    /// [`add_dependency_on_label()`](struct.DispatcherBuilder.html#method.add_dependency_on_label),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if the system does not exist
+    /// * on `build`, if the dependencies form a cycle or a system
+    ///   depends on one which was added after a barrier following it.
+    pub fn with_dependency_on_label(mut self, system: &str, label: &str) -> Self {
+        self.add_dependency_on_label(system, label);
+
+        self
+    }
+
+    /// Makes the system named `system` depend on
+    /// all systems which are tagged with `label`.
+    ///
+    /// See [`with_dependency_on_label()`](struct.DispatcherBuilder.html#method.with_dependency_on_label)
+    /// for details.
+    pub fn add_dependency_on_label(&mut self, system: &str, label: &str) {
+        let to = match self.try_lookup(system) {
+            Ok(id) => id,
+            Err(e) => return self.report(e),
+        };
+
+        if let Some(ids) = self.labels.get(label) {
+            for &from in ids.iter().filter(|&&from| from != to) {
+                self.dependencies.add_dependency(from, to);
+            }
+        }
+    }
+
+    /// Marks the system named `system` as terminal, meaning
+    /// it's expected that no other system depends on it.
+    ///
+    /// With the `log` feature enabled, `build` warns about systems
+    /// which have dependencies, but no dependents, because they are
+    /// often a dependency of another system with a misspelled name.
+    /// Terminal systems are excluded from that warning.
+    ///
+    /// Same as
+    /// [`mark_terminal()`](struct.DispatcherBuilder.html#method.mark_terminal),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system does not exist.
+    pub fn with_terminal(mut self, system: &str) -> Self {
+        self.mark_terminal(system);
+
+        self
+    }
+
+    /// Marks the system named `system` as terminal.
+    ///
+    /// See [`with_terminal()`](struct.DispatcherBuilder.html#method.with_terminal)
+    /// for details.
+    pub fn mark_terminal(&mut self, system: &str) {
+        match self.try_lookup(system) {
+            Ok(id) => self.terminal.push(id),
+            Err(e) => self.report(e),
+        }
+    }
+
+    /// Allows the system named `system` to write the resource `res`
+    /// even though one of its dependencies writes it as well.
+    ///
+    /// With the `log` feature enabled, `build` warns about such
+    /// systems, since the dependency's output is probably overwritten
+    /// by accident.
+    ///
+    /// Same as
+    /// [`allow_rewrite()`](struct.DispatcherBuilder.html#method.allow_rewrite),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system does not exist.
+    pub fn with_allowed_rewrite(mut self, system: &str, res: ResourceId) -> Self {
+        self.allow_rewrite(system, res);
+
+        self
+    }
+
+    /// Allows the system named `system` to write the
+    /// resource `res` after one of its dependencies.
+    ///
+    /// See [`with_allowed_rewrite()`](struct.DispatcherBuilder.html#method.with_allowed_rewrite)
+    /// for details.
+    pub fn allow_rewrite(&mut self, system: &str, res: ResourceId) {
+        match self.try_lookup(system) {
+            Ok(id) => self.allowed_rewrites.push((id, res)),
+            Err(e) => self.report(e),
+        }
+    }
+
+    /// Allows the system named `system` to neither read nor write
+    /// any resources.
+    ///
+    /// With the `log` feature enabled, `build` warns about such
+    /// systems, because they usually lack a `SystemData`.
+    ///
+    /// Same as
+    /// [`allow_no_resources()`](struct.DispatcherBuilder.html#method.allow_no_resources),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the system does not exist.
+    pub fn with_allowed_no_resources(mut self, system: &str) -> Self {
+        self.allow_no_resources(system);
+
+        self
+    }
+
+    /// Allows the system named `system` to not access any resources.
+    ///
+    /// See [`with_allowed_no_resources()`](struct.DispatcherBuilder.html#method.with_allowed_no_resources)
+    /// for details.
+    pub fn allow_no_resources(&mut self, system: &str) {
+        match self.try_lookup(system) {
+            Ok(id) => self.allowed_no_resources.push(id),
+            Err(e) => self.report(e),
+        }
+    }
+
+    /// Makes all of the listed systems read the resource `T`, in
+    /// addition to the resources requested by their `SystemData`.
+    ///
+    /// This is useful for resources like a clock or a configuration,
+    /// which every system may need without listing it in its
+    /// `SystemData` (such systems fetch it themselves, e.g. using
+    /// `with_from_closure`). Systems writing `T` won't run in parallel
+    /// with the listed ones. Systems which already write `T` are
+    /// left unchanged.
+    ///
+    /// Same as
+    /// [`add_read_set()`](struct.DispatcherBuilder.html#method.add_read_set),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the systems does not exist.
+    pub fn with_read_set<T: Resource>(mut self, systems: &[&str]) -> Self {
+        self.add_read_set::<T>(systems);
+
+        self
+    }
+
+    /// Makes all of the listed systems read the resource `T`.
+    ///
+    /// See [`with_read_set()`](struct.DispatcherBuilder.html#method.with_read_set)
+    /// for details.
+    pub fn add_read_set<T: Resource>(&mut self, systems: &[&str]) {
+        self.add_access(ResourceId::new::<T>(), systems, false);
+    }
+
+    /// Makes all of the listed systems write the resource `T`, in
+    /// addition to the resources requested by their `SystemData`.
+    ///
+    /// If one of the systems reads `T` already, that read
+    /// is replaced by the write.
+    ///
+    /// Same as
+    /// [`add_write_set()`](struct.DispatcherBuilder.html#method.add_write_set),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the systems does not exist.
+    pub fn with_write_set<T: Resource>(mut self, systems: &[&str]) -> Self {
+        self.add_write_set::<T>(systems);
+
+        self
+    }
+
+    /// Makes all of the listed systems write the resource `T`.
+    ///
+    /// See [`with_write_set()`](struct.DispatcherBuilder.html#method.with_write_set)
+    /// for details.
+    pub fn add_write_set<T: Resource>(&mut self, systems: &[&str]) {
+        self.add_access(ResourceId::new::<T>(), systems, true);
+    }
+
+    /// Declares the name of a system which will be added later,
+    /// so systems added before it can already depend on it.
+    ///
+    /// This is useful when building dispatchers dynamically, e.g. from
+    /// a configuration file which doesn't list the systems in order.
+    ///
+    /// Same as
+    /// [`declare()`](struct.DispatcherBuilder.html#method.declare),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// On `build`, if a declared system which is depended on was never added.
+    pub fn with_declared(mut self, name: &str) -> Self {
+        self.declare(name);
+
+        self
+    }
+
+    /// Declares the name of a system which will be added later.
+    ///
+    /// See [`with_declared()`](struct.DispatcherBuilder.html#method.with_declared)
+    /// for details.
+    pub fn declare(&mut self, name: &str) {
+        self.declared.push(name.to_owned());
+    }
+
+    /// Makes the system named `to` depend on the system named `from`,
+    /// after both have been registered.
+    ///
+    /// This allows to order systems which don't know about each other
+    /// when they are added, e.g. because they come from different plugins.
+    /// Unlike with the dependency list of `add`, `from` may as
+    /// well have been added after `to`.
+    ///
+    /// Same as
+    /// [`add_dependency()`](struct.DispatcherBuilder.html#method.add_dependency),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// * if one of the systems does not exist
+    /// * on `build`, if the dependencies form a cycle or a system
+    ///   depends on one which was added after a barrier following it.
+    pub fn with_dependency(mut self, from: &str, to: &str) -> Self {
+        self.add_dependency(from, to);
+
+        self
+    }
+
+    /// Makes the system named `to` depend on the system named `from`,
+    /// after both have been registered.
+    ///
+    /// See [`with_dependency()`](struct.DispatcherBuilder.html#method.with_dependency)
+    /// for details.
+    pub fn add_dependency(&mut self, from: &str, to: &str) {
+        let from = self.try_lookup(from);
+        let to = self.try_lookup(to);
+
+        match (from, to) {
+            (Ok(from), Ok(to)) => self.dependencies.add_dependency(from, to),
+            (Err(e), _) | (_, Err(e)) => self.report(e),
+        }
+    }
+
+    /// Limits the work done by a single call to
+    /// [`Dispatcher::dispatch_budgeted`](struct.Dispatcher.html#method.dispatch_budgeted).
+    ///
+    /// Same as
+    /// [`add_budget()`](struct.DispatcherBuilder.html#method.add_budget),
+    /// but returns `self` to enable method chaining.
+    pub fn with_budget(mut self, budget: ExecutionBudget) -> Self {
+        self.add_budget(budget);
+
+        self
+    }
+
+    /// Limits the work done by a single call to
+    /// [`Dispatcher::dispatch_budgeted`](struct.Dispatcher.html#method.dispatch_budgeted).
+    ///
+    /// Other dispatch methods are not affected.
+    pub fn add_budget(&mut self, budget: ExecutionBudget) {
+        self.time_limit = match budget {
+            ExecutionBudget::Unlimited => None,
+            ExecutionBudget::TimeLimit(limit) => Some(limit),
+        };
+    }
+
+    /// Sets the way errors in the system registrations are handled.
+    ///
+    /// Same as
+    /// [`add_error_strategy()`](struct.DispatcherBuilder.html#method.add_error_strategy),
+    /// but returns `self` to enable method chaining.
+    pub fn with_error_strategy(mut self, strategy: ErrorStrategy) -> Self {
+        self.add_error_strategy(strategy);
+
+        self
+    }
+
+    /// Sets the way errors in the system registrations are handled.
+    ///
+    /// With `ErrorStrategy::Collect`, invalid dependencies are ignored
+    /// and systems with duplicate names are registered without a name;
+    /// the errors are returned from
+    /// [`try_build()`](struct.DispatcherBuilder.html#method.try_build).
+    pub fn add_error_strategy(&mut self, strategy: ErrorStrategy) {
+        self.collect_errors = strategy == ErrorStrategy::Collect;
+    }
+
+    /// Attach a rayon thread pool to the builder
+    /// and use that instead of creating one.
+    ///
+    /// Same as
+    /// [`add_pool()`](struct.DispatcherBuilder.html#method.add_pool),
+    /// but returns `self` to enable method chaining.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn with_pool(mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) -> Self {
+        self.add_pool(pool);
+        
+        self
+    }
+
+    /// Attach a rayon thread pool to the builder
+    /// and use that instead of creating one.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn add_pool(&mut self, pool: ::std::sync::Arc<::rayon::ThreadPool>) {
+        self.thread_pool = Some(pool);
+    }
+
+    /// Names the threads of the thread pool created by the dispatcher
+    /// `{name}-worker-{index}`, so they can be told apart in profilers.
+    ///
+    /// This has no effect if a thread pool is passed with `with_pool`.
+    ///
+    /// Same as
+    /// [`add_pool_name()`](struct.DispatcherBuilder.html#method.add_pool_name),
+    /// but returns `self` to enable method chaining.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn with_pool_name(mut self, name: &str) -> Self {
+        self.add_pool_name(name);
+
+        self
+    }
+
+    /// Names the threads of the thread pool created by the dispatcher.
+    ///
+    /// See [`with_pool_name()`](struct.DispatcherBuilder.html#method.with_pool_name)
+    /// for details.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn add_pool_name(&mut self, name: &str) {
+        self.pool_name = Some(name.to_owned());
+    }
+
+    /// Don't create a thread pool when building the `Dispatcher`;
+    /// instead, it will be created lazily on the first parallel dispatch.
+    ///
+    /// This is useful if you want to control when the worker threads
+    /// are spawned, or if the dispatcher might never be used at all.
+    /// It has no effect if a pool is attached with `with_pool` or for
+    /// `build_async`, which always needs a thread pool.
+    ///
+    /// Same as
+    /// [`add_no_default_pool()`](struct.DispatcherBuilder.html#method.add_no_default_pool),
+    /// but returns `self` to enable method chaining.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn no_default_pool(mut self) -> Self {
+        self.add_no_default_pool();
+
+        self
+    }
+
+    /// Don't create a thread pool when building the `Dispatcher`;
+    /// instead, it will be created lazily on the first parallel dispatch.
+    ///
+    /// See [`no_default_pool()`](struct.DispatcherBuilder.html#method.no_default_pool)
+    /// for details.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn add_no_default_pool(&mut self) {
+        self.no_default_pool = true;
+    }
+
+    /// Limits the number of systems which may run at the same time
+    /// to `n`, by creating the thread pool with `n` threads.
+    ///
+    /// See `Dispatcher::set_max_concurrent` for details; unlike that
+    /// method, this doesn't replace a thread pool which already exists.
+    /// It has no effect if a pool is attached with `with_pool`.
+    ///
+    /// Same as
+    /// [`add_max_concurrent()`](struct.DispatcherBuilder.html#method.add_max_concurrent),
+    /// but returns `self` to enable method chaining.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn with_max_concurrent(mut self, n: usize) -> Self {
+        self.add_max_concurrent(n);
+
+        self
+    }
+
+    /// Limits the number of systems which may run at the same time.
+    ///
+    /// See [`with_max_concurrent()`](struct.DispatcherBuilder.html#method.with_max_concurrent)
+    /// for details.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn add_max_concurrent(&mut self, n: usize) {
+        assert!(n > 0, "At least one system has to be able to run");
+
+        self.max_concurrent = Some(n);
+    }
+
+    /// Returns the dependency graph of the systems
+    /// registered so far (thread local systems aren't included).
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.dependencies
+    }
+
+    /// Returns the number of systems added so far,
+    /// not counting thread local, startup and shutdown systems.
+    pub fn count_systems(&self) -> usize {
+        self.dependencies.len()
+    }
+
+    /// Returns the number of systems (see `count_systems`) which
+    /// don't depend on any other system, so they can be run first.
+    pub fn count_ready_systems(&self) -> usize {
+        let deps = &self.dependencies;
+
+        (0..deps.len())
+            .filter(|&id| deps.dependencies_of(id).is_empty())
+            .count()
+    }
+
+    /// Returns a one-line, human-readable summary of the systems
+    /// added so far, which lists
+    ///
+    /// * the number of systems, and how many of them are ready to run
+    ///   first or depend on others (see `count_ready_systems`)
+    /// * the number of resources which are written by some system,
+    ///   and of the ones which are only read
+    /// * the number of levels (see `topological_levels`), which is
+    ///   the length of the longest chain of dependent systems
+    /// * the number of systems in the largest level, which is the most
+    ///   systems that could run in parallel considering only dependencies.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use shred::{DispatcherBuilder, Fetch, System};
+    /// # struct Dummy;
+    /// # impl<'a> System<'a> for Dummy {
+    /// #     type SystemData = Fetch<'a, u32>;
+    /// #     fn run(&mut self, _: Fetch<'a, u32>) {}
+    /// # }
+    /// let builder = DispatcherBuilder::new()
+    ///     .with(Dummy, "a", &[])
+    ///     .with(Dummy, "b", &["a"]);
+    ///
+    /// assert_eq!(
+    ///     builder.summary(),
+    ///     "Systems: 2 (1 ready, 1 dependent), \
+    ///      Resources: 0 read-write / 1 read-only, \
+    ///      Critical path length: 2 systems, \
+    ///      Max theoretical parallelism: 1"
+    /// );
+    /// ```
+    pub fn summary(&self) -> String {
+        let deps = &self.dependencies;
+
+        let systems = self.count_systems();
+        let ready = self.count_ready_systems();
+
+        let resources = deps.resources();
+        let written = resources
+            .iter()
+            .filter(|res| (0..deps.len()).any(|id| deps.writes_of(id).contains(res)))
+            .count();
+
+        let levels = deps.levels();
+        let parallelism = levels.iter().map(Vec::len).max().unwrap_or(0);
+
+        format!(
+            "Systems: {} ({} ready, {} dependent), \
+             Resources: {} read-write / {} read-only, \
+             Critical path length: {} systems, \
+             Max theoretical parallelism: {}",
+            systems,
+            ready,
+            systems - ready,
+            written,
+            resources.len() - written,
+            levels.len(),
+            parallelism
+        )
+    }
+
+    /// Prints the `summary` of the systems added so far to stderr.
+    pub fn print_stats(&self) {
+        eprintln!("{}", self.summary());
+    }
+
+    /// Returns the names of the systems grouped in levels, where the
+    /// systems of a level only depend on systems of earlier levels
+    /// (see [`Dependencies::levels`](struct.Dependencies.html#method.levels)).
+    ///
+    /// This only looks at the dependencies, so systems of the same level
+    /// may still be run one after another if they conflict on resources.
+    pub fn topological_levels(&self) -> Vec<Vec<String>> {
+        let deps = &self.dependencies;
+
+        deps.levels()
+            .into_iter()
+            .map(|level| level.into_iter().map(|id| deps.name(id).to_owned()).collect())
+            .collect()
+    }
+
+    /// Estimates how much faster the systems can be run in parallel
+    /// compared to running them one after another (see `BenchmarkReport`),
+    /// without dispatching anything.
+    ///
+    /// `costs` maps the names of the systems to their estimated cost in
+    /// microseconds; systems which aren't listed are assumed to be free.
+    pub fn benchmark_graph<S>(&self, costs: &HashMap<String, u64, S>) -> BenchmarkReport
+    where
+        S: BuildHasher,
+    {
+        let deps = &self.dependencies;
+
+        benchmark(deps, |id| costs.get(deps.name(id)).cloned().unwrap_or(0))
+    }
+
+    /// Panics if two systems write to the same resource without
+    /// being ordered by a dependency (see
+    /// [`Dependencies::write_write_conflicts`](struct.Dependencies.html#method.write_write_conflicts)).
+    ///
+    /// The panic message lists every conflicting pair of systems
+    /// together with the id of the resource they both write.
+    /// Returns `&Self`, so the check can be done on a builder
+    /// before continuing to use it.
+    pub fn assert_no_conflicts(&self) -> &Self {
+        let deps = &self.dependencies;
+        let conflicts: Vec<_> = deps.write_write_conflicts()
+            .into_iter()
+            .map(|(a, b, res)| {
+                format!(
+                    "\"{}\" and \"{}\" both write {:?}",
+                    deps.name(a),
+                    deps.name(b),
+                    res
+                )
+            })
+            .collect();
+
+        if !conflicts.is_empty() {
+            panic!("Conflicting systems: {}", conflicts.join("; "));
+        }
+
+        self
+    }
+
+    /// Calls `f` with the builder and returns it, so it can
+    /// be inspected in the middle of a chain of `with` calls
+    /// (e.g. to print its `Debug` output).
+    pub fn inspect<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&Self),
+    {
+        f(&self);
+
+        self
+    }
+
+    /// Builds the `Dispatcher`.
+    ///
+    /// In the future, this method will
+    /// precompute useful information in
+    /// order to speed up dispatching.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any errors were collected or the
+    /// dependencies are invalid (see `try_build`).
+    pub fn build(self) -> Dispatcher<'a, 'b> {
+        match self.try_build() {
+            Ok(d) => d,
+            Err(errors) => panic_with(&errors),
+        }
+    }
+
+    /// Builds the `Dispatcher`, returning all the errors
+    /// which were found instead of panicking.
+    ///
+    /// Apart from the errors collected with `ErrorStrategy::Collect`,
+    /// this checks for dependency cycles and systems depending on
+    /// systems which were added after a barrier.
+    pub fn try_build(mut self) -> Result<Dispatcher<'a, 'b>, Vec<BuildError>> {
+        #[cfg(not(target_os = "emscripten"))]
+        use dispatch::dispatcher::PoolConfig;
+        use dispatch::dispatcher::{new_dispatcher, DispatcherParts};
+
+        let stages = self.try_build_stages()?;
+        let parts = DispatcherParts {
+            stages,
+            dependencies: self.dependencies,
+            thread_local: self.thread_local,
+            startup: self.startup,
+            shutdown: self.shutdown,
+            time_limit: self.time_limit,
+        };
+
+        #[cfg(not(target_os = "emscripten"))]
+        let d = {
+            let config = PoolConfig {
+                name: self.pool_name,
+                num_threads: self.max_concurrent,
+                custom: self.thread_pool.is_some(),
+            };
+            let thread_pool = match self.thread_pool {
+                Some(pool) => Some(pool),
+                None if self.no_default_pool => None,
+                None => Some(config.create()),
+            };
+
+            new_dispatcher(parts, thread_pool, config)
+        };
+
+        #[cfg(target_os = "emscripten")]
+        let d = new_dispatcher(parts);
+
+        Ok(d)
+    }
+
+    fn insert(&mut self, entry: SystemEntry<'a>) {
+        if let Some(&res) = entry.reads.iter().find(|res| entry.writes.contains(res)) {
+            let error = BuildError::ReadAndWrite {
+                system: entry.name.clone(),
+                resource: res,
+            };
+            self.report(error);
+        }
+
+        self.dependencies.add(&entry);
+        self.systems.push(entry);
+    }
+
+    /// Warns about systems writing a resource which is also written by
+    /// one of their direct dependencies, unless that was allowed.
+    #[cfg(feature = "log")]
+    fn warn_rewrites(&self) {
+        let deps = &self.dependencies;
+
+        for id in 0..deps.len() {
+            for dep in deps.dependencies_of(id) {
+                let rewritten = deps.writes_of(id)
+                    .iter()
+                    .filter(|res| deps.writes_of(dep.0).contains(res))
+                    .filter(|&&res| !self.allowed_rewrites.contains(&(SystemId(id), res)));
+
+                for res in rewritten {
+                    warn!(
+                        "System \"{}\" writes resource {:?} which was already \
+                         written by its dependency \"{}\"",
+                        deps.name(id),
+                        res,
+                        deps.name(dep.0)
+                    );
+                }
+            }
+        }
+    }
+
+    /// Warns about named systems which don't access
+    /// any resources, unless that was allowed.
+    #[cfg(feature = "log")]
+    fn warn_no_resources(&self) {
+        let deps = &self.dependencies;
+
+        for id in 0..deps.len() {
+            let unused = !deps.name(id).is_empty() &&
+                deps.reads_of(id).is_empty() &&
+                deps.writes_of(id).is_empty() &&
+                !self.allowed_no_resources.contains(&SystemId(id));
+
+            if unused {
+                warn!(
+                    "System \"{}\" declares no resource access; \
+                     did you forget its `SystemData`?",
+                    deps.name(id)
+                );
+            }
+        }
+    }
+
+    /// Adds `res` to the reads (or writes) of the named systems.
+    fn add_access(&mut self, res: ResourceId, systems: &[&str], write: bool) {
+        for name in systems {
+            let id = match self.try_lookup(name) {
+                Ok(id) => id.0,
+                Err(e) => {
+                    self.report(e);
+                    continue;
+                }
+            };
+
+            // Writing a resource includes reading it, so
+            // writes replace reads of the same resource.
+            let entry = &mut self.systems[id];
+            if write {
+                entry.reads.retain(|&r| r != res);
+                if !entry.writes.contains(&res) {
+                    entry.writes.push(res);
+                }
+            } else if !entry.reads.contains(&res) && !entry.writes.contains(&res) {
+                entry.reads.push(res);
+            }
+
+            if write {
+                self.dependencies.add_write(id, res);
+            } else {
+                self.dependencies.add_read(id, res);
+            }
+        }
+    }
+
+    /// Distributes the systems to stages. The systems are inserted
+    /// in the order they were added in, except that systems are
+    /// moved behind dependencies which were added after them.
+    fn try_build_stages(&mut self) -> Result<Vec<Stage<'a>>, Vec<BuildError>> {
+        let barriers = &self.barriers;
+        let segment = |id: usize| barriers.iter().filter(|&&b| b <= id).count();
+
+        let mut errors = ::std::mem::take(&mut self.errors);
+
+        for (to, from) in ::std::mem::take(&mut self.pending) {
+            match self.try_lookup(&from) {
+                Ok(from) => self.dependencies.add_dependency(from, to),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let deps = &self.dependencies;
+        let num = self.systems.len();
+
+        for id in 0..num {
+            for dep in deps.dependencies_of(id) {
+                if segment(dep.0) > segment(id) {
+                    errors.push(BuildError::DependencyAfterBarrier {
+                        system: deps.name(id).to_owned(),
+                        dependency: deps.name(dep.0).to_owned(),
+                    });
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut entries: Vec<_> = self.systems.drain(..).map(Some).collect();
+        let mut inserted = vec![false; num];
+        let mut current_segment = 0;
+        let mut stages_builder = StagesBuilder::default();
+
+        for _ in 0..num {
+            let next = (0..num)
+                .filter(|&id| {
+                    !inserted[id] && deps.dependencies_of(id).iter().all(|dep| inserted[dep.0])
+                })
+                .min_by_key(|&id| (segment(id), id));
+
+            let id = match next {
+                Some(id) => id,
+                None => {
+                    let cycle = (0..num)
+                        .filter(|&id| !inserted[id])
+                        .map(|id| deps.name(id).to_owned())
+                        .collect();
+
+                    return Err(vec![BuildError::Cycle(cycle)]);
+                }
+            };
+
+            if segment(id) != current_segment {
+                current_segment = segment(id);
+                stages_builder.add_barrier();
+            }
+
+            let mut entry = entries[id].take().expect("System inserted twice");
+            debug_assert_eq!(entry.id.0, id);
+            entry.dependencies = deps.dependencies_of(id).iter().cloned().collect();
+
+            inserted[id] = true;
+            stages_builder.insert(entry);
+        }
+
+        #[cfg(feature = "log")]
+        self.warn_orphans();
+        #[cfg(feature = "log")]
+        self.warn_rewrites();
+        #[cfg(feature = "log")]
+        self.warn_no_resources();
+
+        Ok(stages_builder.build())
+    }
+
+    /// Warns about systems which have dependencies, but no dependents
+    /// and weren't marked as terminal.
+    #[cfg(feature = "log")]
+    fn warn_orphans(&self) {
+        let deps = &self.dependencies;
+
+        for id in 0..deps.len() {
+            // Unnamed systems can't be depended on anyway
+            let orphan = !deps.name(id).is_empty() &&
+                !deps.dependencies_of(id).is_empty() &&
+                deps.dependents_of(id).is_empty() &&
+                !self.terminal.contains(&SystemId(id));
+
+            if orphan {
+                warn!(
+                    "System \"{}\" has no dependents and may be an orphan",
+                    deps.name(id)
+                );
+            }
+        }
+    }
+
+    /// Like `try_build_stages`, but panics on errors.
+    fn build_stages(&mut self) -> Vec<Stage<'a>> {
+        match self.try_build_stages() {
+            Ok(stages) => stages,
+            Err(errors) => panic_with(&errors),
+        }
+    }
+
+    /// Returns the id of the system with the name `name`.
+    fn try_lookup(&self, name: &str) -> Result<SystemId, BuildError> {
+        self.map
+            .get(name)
+            .cloned()
+            .ok_or_else(|| BuildError::UnknownSystem(name.to_owned()))
+    }
+
+    /// Panics with `error` or collects it, depending on the error strategy.
+    fn report(&mut self, error: BuildError) {
+        if self.collect_errors {
+            self.errors.push(error);
+        } else {
+            panic!("{}", error);
+        }
+    }
+
+    /// Registers the name of a new system and
+    /// resolves the names of its dependencies.
+    fn register(&mut self, name: &str, dep: &[&str]) -> (SystemId, SmallVec<[SystemId; 4]>) {
+        use std::collections::hash_map::Entry;
+
+        let id = self.next_id();
+
+        let mut dependencies = SmallVec::new();
+        for x in dep {
+            match self.try_lookup(x) {
+                Ok(dep) => dependencies.push(dep),
+                Err(_) if self.declared.iter().any(|d| d == x) => {
+                    self.pending.push((id, (*x).to_owned()))
+                }
+                Err(e) => self.report(e),
+            }
+        }
+
+        if name != "" {
+            if let Entry::Vacant(e) = self.map.entry(name.to_owned()) {
+                e.insert(id);
+            } else {
+                self.report(BuildError::DuplicateName(name.to_owned()));
+            }
+        }
+
+        (id, dependencies)
+    }
+
+    fn next_id(&mut self) -> SystemId {
+        let id = self.current_id;
+        self.current_id += 1;
+
+        SystemId(id)
+    }
+}
+
+/// A flat description of a closure system (see
+/// [`DispatcherBuilder::add_from_closure`]), which can be used
+/// to build a `DispatcherBuilder` from an iterator.
+///
+/// The systems may be listed in any order; dependencies on systems
+/// listed later are resolved when the dispatcher is built.
+///
+/// # Examples
+///
+/// ```rust
+/// use shred::{DispatcherBuilder, RegisteredSystem, ResourceId, Resources};
+///
+/// let systems = vec![
+///     RegisteredSystem {
+///         name: "print".to_owned(),
+///         reads: vec![ResourceId::new::<u32>()],
+///         writes: vec![],
+///         deps: vec!["double".to_owned()],
+///         run: Box::new(|res: &Resources| println!("{}", *res.fetch::<u32>(0))),
+///     },
+///     RegisteredSystem {
+///         name: "double".to_owned(),
+///         reads: vec![],
+///         writes: vec![ResourceId::new::<u32>()],
+///         deps: vec![],
+///         run: Box::new(|res: &Resources| *res.fetch_mut::<u32>(0) *= 2),
+///     },
+/// ];
+///
+/// let builder: DispatcherBuilder = systems.into_iter().collect();
+///
+/// let mut res = Resources::new();
+/// res.add(5u32);
+/// builder.build().dispatch(&res);
+/// assert_eq!(*res.fetch::<u32>(0), 10);
+/// ```
+///
+/// [`DispatcherBuilder::add_from_closure`]: struct.DispatcherBuilder.html#method.add_from_closure
+pub struct RegisteredSystem<'a> {
+    /// The name of the system.
+    pub name: String,
+    /// The resources the system reads.
+    pub reads: Vec<ResourceId>,
+    /// The resources the system writes.
+    pub writes: Vec<ResourceId>,
+    /// The names of the systems this system depends on.
+    pub deps: Vec<String>,
+    /// The function running the system.
+    pub run: Box<dyn FnMut(&Resources) + Send + 'a>,
+}
+
+impl<'a, 'b> FromIterator<RegisteredSystem<'a>> for DispatcherBuilder<'a, 'b> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = RegisteredSystem<'a>>,
+    {
+        let systems: Vec<_> = iter.into_iter().collect();
+        let mut builder = DispatcherBuilder::with_capacity(systems.len());
+
+        for system in &systems {
+            builder.declare(&system.name);
+        }
+
+        for system in systems {
+            let deps: Vec<_> = system.deps.iter().map(String::as_str).collect();
+            builder.add_from_closure(system.reads, system.writes, system.run, &system.name, &deps);
+        }
+
+        builder
+    }
+}
+
+impl<'a, 'b> fmt::Debug for DispatcherBuilder<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DispatcherBuilder")
+            .field("systems", &self.dependencies)
+            .field("thread_local", &self.thread_local.len())
+            .finish()
+    }
+}
+
+fn panic_with(errors: &[BuildError]) -> ! {
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+
+    panic!("{}", messages.join("; "))
+}
+
+/// A system created from a closure by `add_from_closure`.
+struct ClosureSystem<F>(F);
+
+impl<'a, F> RunNow<'a> for ClosureSystem<F>
+where
+    F: FnMut(&Resources),
+{
+    fn run_now(&mut self, res: &'a Resources) {
+        (self.0)(res);
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+impl<'b> DispatcherBuilder<'static, 'b> {
+    /// Builds an async dispatcher.
+    ///
+    /// It does not allow non-static types and
+    /// accepts a `Resource` struct.
+    ///
+    /// # Panics
+    ///
+    /// Panics if startup or shutdown systems were added,
+    /// since they are only supported by `Dispatcher`.
+    pub fn build_async<R>(mut self, res: R) -> ::dispatch::async::AsyncDispatcher<'b, R> {
+        use dispatch::async::new_async;
+        use dispatch::dispatcher::create_thread_pool;
+
+        assert!(
+            self.startup.is_empty() && self.shutdown.is_empty(),
+            "Startup and shutdown systems are not supported by `AsyncDispatcher`"
+        );
+
+        let stages = self.build_stages();
 
         new_async(
             res,
-            self.stages_builder.build(),
+            stages,
             self.thread_local,
-            self.thread_pool.unwrap_or_else(Self::create_thread_pool),
+            match self.thread_pool {
+                Some(pool) => pool,
+                None => create_thread_pool(self.pool_name.as_deref(), self.max_concurrent),
+            },
         )
     }
 }
@@ -0,0 +1,60 @@
+use std::error::Error;
+use std::fmt::{Display, Error as FormatError, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A token which can be used to cancel a dispatch
+/// started with [`Dispatcher::dispatch_cancellable`].
+///
+/// Cloning the token gives you another handle to the
+/// same flag, so it can be moved into a system or
+/// another thread.
+///
+/// [`Dispatcher::dispatch_cancellable`]: struct.Dispatcher.html#method.dispatch_cancellable
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token which is not cancelled.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancels the token. No new systems will be started
+    /// by dispatches using this token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if `cancel` has been called on this
+    /// token or any of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Returned by [`Dispatcher::dispatch_cancellable`] if
+/// systems had to be skipped because the token was cancelled.
+///
+/// [`Dispatcher::dispatch_cancellable`]: struct.Dispatcher.html#method.dispatch_cancellable
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cancelled {
+    /// The number of systems which were run
+    /// before the rest had to be skipped.
+    pub ran: usize,
+}
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FormatError> {
+        write!(f, "The dispatch was cancelled after {} systems were run", self.ran)
+    }
+}
+
+impl Error for Cancelled {
+    fn description(&self) -> &str {
+        "This error is returned when a dispatch was cancelled \
+         before all systems could be run"
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use smallvec::SmallVec;
+
+use dispatch::dispatcher::SystemId;
+use dispatch::stage::SystemEntry;
+use res::ResourceId;
+
+/// The names, dependencies and resource accesses
+/// of the systems registered with a `DispatcherBuilder`,
+/// indexed by system id.
+#[derive(Clone, Default)]
+pub struct Dependencies {
+    names: Vec<String>,
+    dependencies: Vec<SmallVec<[SystemId; 4]>>,
+    dependents: Vec<SmallVec<[SystemId; 4]>>,
+    reads: Vec<Vec<ResourceId>>,
+    writes: Vec<Vec<ResourceId>>,
+}
+
+impl Dependencies {
+    /// Creates an empty graph with space for `systems` systems.
+    pub fn with_capacity(systems: usize) -> Self {
+        Dependencies {
+            names: Vec::with_capacity(systems),
+            dependencies: Vec::with_capacity(systems),
+            dependents: Vec::with_capacity(systems),
+            reads: Vec::with_capacity(systems),
+            writes: Vec::with_capacity(systems),
+        }
+    }
+
+    /// Records the system described by `entry`.
+    pub(crate) fn add(&mut self, entry: &SystemEntry) {
+        debug_assert_eq!(entry.id.0, self.names.len());
+
+        self.names.push(entry.name.clone());
+        self.dependencies.push(SmallVec::new());
+        self.dependents.push(SmallVec::new());
+        self.reads.push(entry.reads.clone());
+        self.writes.push(entry.writes.clone());
+
+        for &dep in &entry.dependencies {
+            self.add_dependency(dep, entry.id);
+        }
+    }
+
+    /// Makes `to` depend on `from`.
+    pub(crate) fn add_dependency(&mut self, from: SystemId, to: SystemId) {
+        let dependencies = &mut self.dependencies[to.0];
+
+        if !dependencies.contains(&from) {
+            dependencies.push(from);
+            self.dependents[from.0].push(to);
+        }
+    }
+
+    /// Makes the system `id` read the resource `res`,
+    /// unless it writes it already.
+    pub(crate) fn add_read(&mut self, id: usize, res: ResourceId) {
+        if !self.reads[id].contains(&res) && !self.writes[id].contains(&res) {
+            self.reads[id].push(res);
+        }
+    }
+
+    /// Makes the system `id` write the resource `res`,
+    /// replacing a read of it.
+    pub(crate) fn add_write(&mut self, id: usize, res: ResourceId) {
+        self.reads[id].retain(|&r| r != res);
+        if !self.writes[id].contains(&res) {
+            self.writes[id].push(res);
+        }
+    }
+
+    /// Returns the number of systems.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if there are no systems.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the (sorted) ids of all resources
+    /// which are read or written by any system.
+    pub fn resources(&self) -> Vec<ResourceId> {
+        let mut resources: Vec<_> = self.reads
+            .iter()
+            .chain(self.writes.iter())
+            .flat_map(|ids| ids.iter().cloned())
+            .collect();
+        resources.sort();
+        resources.dedup();
+
+        resources
+    }
+
+    /// Returns the direct dependencies of the system `id`.
+    pub fn dependencies_of(&self, id: usize) -> &[SystemId] {
+        &self.dependencies[id]
+    }
+
+    /// Returns the systems which directly depend on the system `id`.
+    pub fn dependents_of(&self, id: usize) -> &[SystemId] {
+        &self.dependents[id]
+    }
+
+    /// Returns the resources read by the system `id`.
+    pub fn reads_of(&self, id: usize) -> &[ResourceId] {
+        &self.reads[id]
+    }
+
+    /// Returns the resources written by the system `id`.
+    pub fn writes_of(&self, id: usize) -> &[ResourceId] {
+        &self.writes[id]
+    }
+
+    /// Returns all dependency edges as `(dependent, dependency)` pairs,
+    /// grouped by the depending system.
+    pub fn edges<'s>(&'s self) -> impl Iterator<Item = (usize, usize)> + 's {
+        self.dependencies
+            .iter()
+            .enumerate()
+            .flat_map(|(id, deps)| deps.iter().map(move |dep| (id, dep.0)))
+    }
+
+    /// Returns all dependency edges as `(dependency, dependent)` pairs,
+    /// grouped by the system which is depended on.
+    pub fn forward_edges<'s>(&'s self) -> impl Iterator<Item = (usize, usize)> + 's {
+        self.dependents
+            .iter()
+            .enumerate()
+            .flat_map(|(id, deps)| deps.iter().map(move |dep| (id, dep.0)))
+    }
+
+    /// Returns the dependency graph as a matrix, where
+    /// `matrix[i][j]` is `true` if the system `j` directly
+    /// depends on the system `i`.
+    pub fn to_adjacency_matrix(&self) -> Vec<Vec<bool>> {
+        let num = self.len();
+        let mut matrix = vec![vec![false; num]; num];
+
+        for (from, to) in self.forward_edges() {
+            matrix[from][to] = true;
+        }
+
+        matrix
+    }
+
+    /// Returns all dependency edges as `(dependency, dependent)` pairs,
+    /// like `forward_edges`.
+    pub fn to_edge_list(&self) -> Vec<(usize, usize)> {
+        self.forward_edges().collect()
+    }
+
+    /// Returns the name of the system `id`.
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    /// Returns the (sorted) ids of all systems the system `id`
+    /// depends on, either directly or transitively.
+    pub fn ancestors(&self, id: usize) -> Vec<usize> {
+        collect_reachable(&self.dependencies, id)
+    }
+
+    /// Returns the (sorted) ids of all systems which depend
+    /// on the system `id`, either directly or transitively.
+    pub fn descendants(&self, id: usize) -> Vec<usize> {
+        collect_reachable(&self.dependents, id)
+    }
+
+    /// Returns `true` if the system `to` depends on the
+    /// system `from`, either directly or transitively.
+    pub fn reachable_from(&self, from: usize, to: usize) -> bool {
+        let mut visited = vec![false; self.names.len()];
+        let mut stack = vec![to];
+
+        while let Some(id) = stack.pop() {
+            for dep in &self.dependencies[id] {
+                if dep.0 == from {
+                    return true;
+                }
+
+                if !visited[dep.0] {
+                    visited[dep.0] = true;
+                    stack.push(dep.0);
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Partitions the systems into levels, so that the systems of a level
+    /// only depend on systems of earlier levels. Every system is put in
+    /// the earliest level possible; within a level, systems are ordered
+    /// by id.
+    ///
+    /// Only the dependencies are taken into account, not the resources
+    /// or barriers. Systems which are part of a dependency cycle (or
+    /// depend on one) are left out.
+    pub fn levels(&self) -> Vec<Vec<usize>> {
+        let num = self.len();
+        let mut assigned = vec![false; num];
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+
+        loop {
+            let level: Vec<_> = (0..num)
+                .filter(|&id| {
+                    !assigned[id] && self.dependencies[id].iter().all(|dep| assigned[dep.0])
+                })
+                .collect();
+
+            if level.is_empty() {
+                return levels;
+            }
+
+            for &id in &level {
+                assigned[id] = true;
+            }
+            levels.push(level);
+        }
+    }
+
+    /// Returns all pairs of systems which write to the same
+    /// resource, but aren't ordered by a (transitive) dependency.
+    ///
+    /// Such systems never run at the same time, but the order
+    /// they run in only depends on the order they were added in.
+    ///
+    /// The entries are sorted by resource id; the smaller
+    /// system id always comes first.
+    pub fn write_write_conflicts(&self) -> Vec<(usize, usize, ResourceId)> {
+        let mut rev_writes: BTreeMap<ResourceId, Vec<usize>> = BTreeMap::new();
+        for (id, writes) in self.writes.iter().enumerate() {
+            for &res in writes {
+                rev_writes.entry(res).or_default().push(id);
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        for (&res, writers) in &rev_writes {
+            for (i, &a) in writers.iter().enumerate() {
+                for &b in &writers[i + 1..] {
+                    if !self.reachable_from(a, b) && !self.reachable_from(b, a) {
+                        conflicts.push((a, b, res));
+                    }
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+/// Returns the systems reachable from `start` by following `edges`.
+fn collect_reachable(edges: &[SmallVec<[SystemId; 4]>], start: usize) -> Vec<usize> {
+    let mut visited = vec![false; edges.len()];
+    let mut stack = vec![start];
+
+    while let Some(id) = stack.pop() {
+        for next in &edges[id] {
+            if !visited[next.0] {
+                visited[next.0] = true;
+                stack.push(next.0);
+            }
+        }
+    }
+
+    (0..edges.len()).filter(|&id| visited[id]).collect()
+}
+
+impl fmt::Debug for Dependencies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.names.len()).map(|id| SystemDebug { deps: self, id }))
+            .finish()
+    }
+}
+
+struct SystemDebug<'a> {
+    deps: &'a Dependencies,
+    id: usize,
+}
+
+impl<'a> fmt::Debug for SystemDebug<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let deps = self.deps;
+        let dependencies: Vec<_> = deps.dependencies[self.id]
+            .iter()
+            .map(|dep| &deps.names[dep.0])
+            .collect();
+
+        f.debug_struct("System")
+            .field("name", &deps.names[self.id])
+            .field("dependencies", &dependencies)
+            .field("reads", &deps.reads[self.id])
+            .field("writes", &deps.writes[self.id])
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dispatch::builder::DispatcherBuilder;
+    use res::{FetchMut, ResourceId};
+    use system::System;
+
+    struct ResA;
+    struct ResB;
+
+    struct WriteA;
+
+    impl<'a> System<'a> for WriteA {
+        type SystemData = FetchMut<'a, ResA>;
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    struct WriteB;
+
+    impl<'a> System<'a> for WriteB {
+        type SystemData = FetchMut<'a, ResB>;
+
+        fn run(&mut self, _: Self::SystemData) {}
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut builder = DispatcherBuilder::with_capacity(500);
+        for _ in 0..500 {
+            builder.add(WriteA, "", &[]);
+        }
+
+        let deps = builder.dependencies();
+        assert_eq!(deps.names.capacity(), 500);
+        assert_eq!(deps.dependencies.capacity(), 500);
+        assert_eq!(deps.reads.capacity(), 500);
+        assert_eq!(deps.writes.capacity(), 500);
+    }
+
+    #[test]
+    fn edges() {
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a", &[])
+            .with(WriteA, "b", &["a", "a"])
+            .with(WriteB, "c", &[])
+            .with(WriteA, "d", &["b", "c"])
+            .with_dependency("c", "b");
+        let deps = builder.dependencies();
+
+        let edges: Vec<_> = deps.edges().collect();
+        assert_eq!(edges, vec![(1, 0), (1, 2), (3, 1), (3, 2)]);
+
+        let forward: Vec<_> = deps.forward_edges().collect();
+        assert_eq!(forward, vec![(0, 1), (1, 3), (2, 3), (2, 1)]);
+    }
+
+    #[test]
+    fn levels() {
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a", &[])
+            .with(WriteA, "b", &["a"])
+            .with(WriteA, "c", &["a"])
+            .with(WriteA, "d", &["b", "c"]);
+        assert_eq!(builder.dependencies().levels(), vec![vec![0], vec![1, 2], vec![3]]);
+
+        let builder = builder.with_dependency("b", "c");
+        assert_eq!(
+            builder.dependencies().levels(),
+            vec![vec![0], vec![1], vec![2], vec![3]]
+        );
+    }
+
+    #[test]
+    fn ancestors_and_descendants() {
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a", &[])
+            .with(WriteA, "b", &["a"])
+            .with(WriteA, "c", &["a"])
+            .with(WriteA, "d", &["b", "c"])
+            .with(WriteB, "e", &[]);
+        let deps = builder.dependencies();
+
+        assert_eq!(deps.ancestors(3), vec![0, 1, 2]);
+        assert_eq!(deps.ancestors(1), vec![0]);
+        assert!(deps.ancestors(0).is_empty());
+        assert_eq!(deps.descendants(0), vec![1, 2, 3]);
+        assert!(deps.descendants(3).is_empty());
+        assert!(deps.descendants(4).is_empty());
+    }
+
+    #[test]
+    fn adjacency_matrix() {
+        /// Kahn's algorithm; returns `true` if the graph has a cycle.
+        fn has_cycle(matrix: &[Vec<bool>]) -> bool {
+            let num = matrix.len();
+            let mut in_degree: Vec<_> = (0..num)
+                .map(|to| (0..num).filter(|&from| matrix[from][to]).count())
+                .collect();
+            let mut ready: Vec<_> = (0..num).filter(|&id| in_degree[id] == 0).collect();
+            let mut visited = 0;
+
+            while let Some(from) = ready.pop() {
+                visited += 1;
+                for to in (0..num).filter(|&to| matrix[from][to]) {
+                    in_degree[to] -= 1;
+                    if in_degree[to] == 0 {
+                        ready.push(to);
+                    }
+                }
+            }
+
+            visited != num
+        }
+
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a", &[])
+            .with(WriteA, "b", &["a"])
+            .with(WriteB, "c", &[])
+            .with(WriteA, "d", &["b", "c"]);
+        let deps = builder.dependencies();
+        let matrix = deps.to_adjacency_matrix();
+
+        assert!(matrix[0][1]);
+        assert!(matrix[1][3]);
+        assert!(matrix[2][3]);
+        assert!(!matrix[1][0]);
+        assert!(!matrix[0][3]);
+        assert_eq!(deps.to_edge_list(), vec![(0, 1), (1, 3), (2, 3)]);
+
+        let levels: usize = deps.levels().iter().map(Vec::len).sum();
+        assert!(!has_cycle(&matrix));
+        assert_eq!(levels, deps.len());
+
+        let builder = builder.with_dependency("d", "a");
+        let deps = builder.dependencies();
+        let levels: usize = deps.levels().iter().map(Vec::len).sum();
+        assert!(has_cycle(&deps.to_adjacency_matrix()));
+        assert!(levels < deps.len());
+    }
+
+    #[test]
+    fn reachable_from() {
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a", &[])
+            .with(WriteA, "b", &["a"])
+            .with(WriteA, "c", &["b"])
+            .with(WriteB, "d", &[]);
+        let deps = builder.dependencies();
+
+        assert!(deps.reachable_from(0, 2));
+        assert!(deps.reachable_from(1, 2));
+        assert!(!deps.reachable_from(2, 0));
+        assert!(!deps.reachable_from(0, 3));
+    }
+
+    #[test]
+    fn write_write_conflicts() {
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a0", &[])
+            .with(WriteA, "a1", &[])
+            .with(WriteB, "b0", &[])
+            .with(WriteB, "b1", &[])
+            .with_dependency("a0", "a1");
+
+        let conflicts = builder.dependencies().write_write_conflicts();
+        assert_eq!(conflicts, vec![(2, 3, ResourceId::new::<ResB>())]);
+
+        let builder = DispatcherBuilder::new()
+            .with(WriteA, "a0", &[])
+            .with(WriteB, "b0", &[])
+            .with(WriteA, "a1", &[])
+            .with(WriteB, "b1", &[]);
+
+        let mut conflicts = builder.dependencies().write_write_conflicts();
+        conflicts.sort_by_key(|&(a, b, _)| (a, b));
+        assert_eq!(
+            conflicts,
+            vec![
+                (0, 2, ResourceId::new::<ResA>()),
+                (1, 3, ResourceId::new::<ResB>()),
+            ]
+        );
+    }
+}
@@ -1,6 +1,12 @@
 use smallvec::SmallVec;
 
+use dispatch::budget::DispatchOutcome;
+use dispatch::cancel::{CancellationToken, Cancelled};
+use dispatch::dependencies::Dependencies;
+use dispatch::error::DispatchError;
 use dispatch::stage::Stage;
+#[cfg(not(target_os = "emscripten"))]
+use dispatch::stats::{DispatchStats, StatsRecorder};
 use res::Resources;
 use system::RunNow;
 
@@ -8,8 +14,17 @@ use system::RunNow;
 /// systems to be executed in parallel.
 pub struct Dispatcher<'a, 'b> {
     stages: Vec<Stage<'a>>,
+    dependencies: Dependencies,
     thread_local: ThreadLocal<'b>,
-    #[cfg(not(target_os = "emscripten"))] thread_pool: ::std::sync::Arc<::rayon::ThreadPool>,
+    startup: ThreadLocal<'b>,
+    shutdown: ThreadLocal<'b>,
+    shut_down: bool,
+    next_step: usize,
+    current_stage: usize,
+    time_limit: Option<::std::time::Duration>,
+    last_dispatch_duration: ::std::time::Duration,
+    #[cfg(not(target_os = "emscripten"))] thread_pool: Option<::std::sync::Arc<::rayon::ThreadPool>>,
+    #[cfg(not(target_os = "emscripten"))] pool_config: PoolConfig,
 }
 
 impl<'a, 'b> Dispatcher<'a, 'b> {
@@ -29,6 +44,10 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// [`dispatch_par`]: struct.Dispatcher.html#method.dispatch_par
     /// [`dispatch_seq`]: struct.Dispatcher.html#method.dispatch_seq
     pub fn dispatch(&mut self, res: &Resources) {
+        use std::time::Instant;
+
+        let start = Instant::now();
+
         #[cfg(not(target_os = "emscripten"))]
         self.dispatch_par(res);
 
@@ -36,6 +55,185 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
         self.dispatch_seq(res);
 
         self.dispatch_thread_local(res);
+        self.last_dispatch_duration = start.elapsed();
+    }
+
+    /// Returns how long the last call to `dispatch` took, including
+    /// thread local (and startup) systems; zero if it wasn't called yet.
+    ///
+    /// Other ways to dispatch don't update this duration.
+    pub fn last_dispatch_duration(&self) -> ::std::time::Duration {
+        self.last_dispatch_duration
+    }
+
+    /// Like `dispatch`, but stops starting new systems once the
+    /// `ExecutionBudget` configured with `DispatcherBuilder::with_budget`
+    /// is exceeded.
+    ///
+    /// Systems which are already running are allowed to finish.
+    /// The next call continues where this one stopped, so every system
+    /// is run exactly once per full cycle. At least one system is run
+    /// per call, so repeated calls always make progress. Thread local
+    /// systems are run at the end of a cycle, once all other systems ran.
+    ///
+    /// Dispatching in any other way (or calling `reset`) in between
+    /// abandons an interrupted cycle; the next call starts a new one.
+    ///
+    /// Since a call may only run some of the systems, the outcome
+    /// tells how many (or which) systems were run by this call.
+    ///
+    /// Please note that this method assumes that no resource
+    /// is currently borrowed. If that's the case, it panics.
+    pub fn dispatch_budgeted(&mut self, res: &Resources) -> DispatchOutcome {
+        use std::sync::atomic::AtomicBool;
+        use std::time::Instant;
+
+        self.run_startup(res);
+        self.next_step = 0;
+
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let progressed = AtomicBool::new(false);
+        let mut completed = Vec::new();
+
+        while self.current_stage < self.stages.len() {
+            let finished = {
+                let stage = &mut self.stages[self.current_stage];
+                let progressed = &progressed;
+                let completed = &mut completed;
+                let run = move || stage.execute_until(res, deadline, progressed, completed);
+
+                #[cfg(not(target_os = "emscripten"))]
+                let finished = lazy_pool(&mut self.thread_pool, &self.pool_config).install(run);
+
+                #[cfg(target_os = "emscripten")]
+                let finished = run();
+
+                finished
+            };
+
+            if !finished {
+                let mut pending = Vec::new();
+                for stage in &self.stages[self.current_stage..] {
+                    stage.pending(&mut pending);
+                }
+
+                return DispatchOutcome::BudgetExceeded { completed, pending };
+            }
+
+            self.current_stage += 1;
+        }
+
+        self.current_stage = 0;
+        self.dispatch_thread_local(res);
+
+        DispatchOutcome::Completed {
+            ran: completed.len() + self.thread_local.len(),
+        }
+    }
+
+    /// Like `dispatch`, but returns an error instead of panicking
+    /// if one of the systems panics.
+    ///
+    /// If a system panics, the remaining systems of its group, later
+    /// stages and thread local systems are skipped. Other groups of
+    /// the same stage still run to completion, since they run in
+    /// parallel. If multiple systems panic, the error of one of them
+    /// is returned. The panic message is still printed by the panic hook.
+    ///
+    /// Please note that this method assumes that no resource
+    /// is currently borrowed. If that's the case, it panics.
+    pub fn try_dispatch(&mut self, res: &Resources) -> Result<(), DispatchError> {
+        use std::panic::{self, AssertUnwindSafe};
+
+        use dispatch::stage::panic_message;
+
+        self.prepare(res);
+
+        {
+            let stages = &mut self.stages;
+            let run = move || {
+                for stage in stages {
+                    stage.try_execute(res)?;
+                }
+
+                Ok(())
+            };
+
+            #[cfg(not(target_os = "emscripten"))]
+            lazy_pool(&mut self.thread_pool, &self.pool_config).install(run)?;
+
+            #[cfg(target_os = "emscripten")]
+            run()?;
+        }
+
+        for (index, sys) in self.thread_local.iter_mut().enumerate() {
+            panic::catch_unwind(AssertUnwindSafe(|| sys.run_now(res))).map_err(|payload| {
+                DispatchError::ThreadLocalPanic {
+                    index,
+                    message: panic_message(&*payload).into(),
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Like `dispatch`, but stops starting new systems
+    /// as soon as `token` gets cancelled.
+    ///
+    /// Systems which are already running when the token is
+    /// cancelled are allowed to finish; this method only returns
+    /// once they did. If any system (including thread local ones)
+    /// had to be skipped, a `Cancelled` error is returned.
+    ///
+    /// Both the `Ok` value and the error tell how many systems
+    /// (including thread local ones) were run; skipped
+    /// systems are not counted.
+    ///
+    /// Please note that this method assumes that no resource
+    /// is currently borrowed. If that's the case, it panics.
+    pub fn dispatch_cancellable(
+        &mut self,
+        res: &Resources,
+        token: &CancellationToken,
+    ) -> Result<usize, Cancelled> {
+        self.prepare(res);
+
+        let mut ran = {
+            let stages = &mut self.stages;
+            let run = move || {
+                let mut ran = 0;
+                for stage in stages {
+                    let stage_ran = stage.execute_cancellable(res, token);
+                    ran += stage_ran;
+
+                    if stage_ran < stage.len() {
+                        return Err(Cancelled { ran });
+                    }
+                }
+
+                Ok(ran)
+            };
+
+            #[cfg(not(target_os = "emscripten"))]
+            let ran = lazy_pool(&mut self.thread_pool, &self.pool_config).install(run)?;
+
+            #[cfg(target_os = "emscripten")]
+            let ran = run()?;
+
+            ran
+        };
+
+        for sys in &mut self.thread_local {
+            if token.is_cancelled() {
+                return Err(Cancelled { ran });
+            }
+
+            sys.run_now(res);
+            ran += 1;
+        }
+
+        Ok(ran)
     }
 
     /// Dispatches the systems (except thread local systems)
@@ -51,11 +249,45 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// is currently borrowed. If that's the case, it panics.
     #[cfg(not(target_os = "emscripten"))]
     pub fn dispatch_par(&mut self, res: &Resources) {
+        self.prepare(res);
+
         let stages = &mut self.stages;
 
-        self.thread_pool.install(move || for stage in stages {
-            stage.execute(res);
-        });
+        lazy_pool(&mut self.thread_pool, &self.pool_config)
+            .install(move || for stage in stages {
+                stage.execute(res);
+            });
+    }
+
+    /// Like `dispatch`, but measures how long the systems took to execute.
+    ///
+    /// The returned statistics only cover the systems executed in parallel;
+    /// thread local systems are run afterwards, as usual.
+    ///
+    /// Only available on platforms with
+    /// multithreading support (so not on emscripten).
+    ///
+    /// Please note that this method assumes that no resource
+    /// is currently borrowed. If that's the case, it panics.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn collect_stats(&mut self, res: &Resources) -> DispatchStats {
+        self.prepare(res);
+
+        let stages = &mut self.stages;
+        let recorder = StatsRecorder::new();
+
+        {
+            let recorder = &recorder;
+            lazy_pool(&mut self.thread_pool, &self.pool_config)
+                .install(move || for stage in stages {
+                    stage.execute_recorded(res, recorder);
+                });
+        }
+
+        let stats = recorder.finish();
+        self.dispatch_thread_local(res);
+
+        stats
     }
 
     /// Dispatches the systems (except thread local systems) sequentially.
@@ -66,49 +298,316 @@ impl<'a, 'b> Dispatcher<'a, 'b> {
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch_seq(&mut self, res: &Resources) {
+        self.prepare(res);
+
         for stage in &mut self.stages {
             stage.execute_seq(res);
         }
     }
 
+    /// Runs a single system on the current thread and returns
+    /// information about it; once all systems ran, `None` is
+    /// returned and the next call starts over with the first system.
+    ///
+    /// Calling this until it returns `None` runs the systems in
+    /// a valid order, equivalent to `dispatch_seq` followed by
+    /// `dispatch_thread_local`. This is mainly useful for debugging.
+    ///
+    /// Dispatching in any other way (or calling `reset`) in between
+    /// starts over with the first system.
+    ///
+    /// Please note that this method assumes that no resource
+    /// is currently borrowed. If that's the case, it panics.
+    pub fn step(&mut self, res: &Resources) -> Option<DispatchStep> {
+        self.run_startup(res);
+        self.clear_budgeted();
+
+        let total = self.stages.iter().map(Stage::len).sum::<usize>() + self.thread_local.len();
+        let current = self.next_step;
+
+        if current == total {
+            self.next_step = 0;
+
+            return None;
+        }
+
+        self.next_step += 1;
+        let pending = self.next_step < total;
+
+        let mut index = current;
+        for stage in &mut self.stages {
+            let len = stage.len();
+
+            if index < len {
+                let (id, name) = stage.run_single(index, res).expect("Index checked above");
+
+                return Some(DispatchStep {
+                    name: name.to_owned(),
+                    id: Some(id.0),
+                    pending,
+                });
+            }
+
+            index -= len;
+        }
+
+        self.thread_local[index].run_now(res);
+
+        Some(DispatchStep {
+            name: String::new(),
+            id: None,
+            pending,
+        })
+    }
+
+    /// Limits the number of systems which may run at the same time
+    /// to `n`, by running them on a thread pool of `n` threads.
+    ///
+    /// This is useful if systems need a lot of memory while running.
+    /// `set_max_concurrent(1)` runs one system after another, like
+    /// `dispatch_seq`. Only available on platforms with
+    /// multithreading support (so not on emscripten).
+    ///
+    /// The thread pool is created on the next parallel dispatch;
+    /// a pool the dispatcher created before is dropped. To avoid
+    /// creating that pool in the first place, use
+    /// `DispatcherBuilder::with_max_concurrent` instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero or if the thread pool was
+    /// passed with `DispatcherBuilder::with_pool`.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn set_max_concurrent(&mut self, n: usize) {
+        assert!(n > 0, "At least one system has to be able to run");
+        assert!(
+            !self.pool_config.custom,
+            "Can't limit the number of threads of a pool passed with `with_pool`"
+        );
+
+        if self.pool_config.num_threads != Some(n) {
+            self.pool_config.num_threads = Some(n);
+            self.thread_pool = None;
+        }
+    }
+
+    /// Returns the dependency graph of the systems (thread local,
+    /// startup and shutdown systems aren't included).
+    ///
+    /// This includes dependencies added with `add_dependency`
+    /// or on declared systems.
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.dependencies
+    }
+
+    /// Returns the number of systems, not counting
+    /// thread local, startup and shutdown systems.
+    pub fn num_systems(&self) -> usize {
+        self.dependencies.len()
+    }
+
+    /// Returns the number of distinct resources the
+    /// systems (see `num_systems`) read or write.
+    pub fn num_resources(&self) -> usize {
+        self.dependencies.resources().len()
+    }
+
+    /// Calls `reset` on all systems, including thread local ones,
+    /// so they can clear any state they keep between dispatches.
+    ///
+    /// An interrupted `dispatch_budgeted` or `step` cycle is abandoned
+    /// as well, so the next call starts over with the first system.
+    pub fn reset(&mut self) {
+        self.clear_budgeted();
+        self.next_step = 0;
+
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+
+        for sys in &mut self.thread_local {
+            sys.reset_now();
+        }
+    }
+
     /// Dispatch only thread local systems sequentially.
     ///
     /// Please note that this method assumes that no resource
     /// is currently borrowed. If that's the case, it panics.
     pub fn dispatch_thread_local(&mut self, res: &Resources) {
+        self.assert_not_shut_down();
+
         for sys in &mut self.thread_local {
             sys.run_now(res);
         }
     }
+
+    /// Runs the shutdown systems (see `DispatcherBuilder::add_shutdown`)
+    /// in the order they were added in, which respects their dependencies.
+    ///
+    /// Afterwards, the dispatcher can't be used anymore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dispatcher has been shut down already.
+    pub fn shutdown(&mut self, res: &Resources) {
+        self.assert_not_shut_down();
+        self.shut_down = true;
+
+        for mut sys in self.shutdown.drain() {
+            sys.run_now(res);
+        }
+    }
+
+    /// Runs the startup systems (see `run_startup`) and abandons
+    /// interrupted `dispatch_budgeted` and `step` cycles.
+    fn prepare(&mut self, res: &Resources) {
+        self.run_startup(res);
+        self.clear_budgeted();
+        self.next_step = 0;
+    }
+
+    /// Runs the startup systems (on the first call only)
+    /// and drops them afterwards.
+    fn run_startup(&mut self, res: &Resources) {
+        self.assert_not_shut_down();
+
+        for mut sys in self.startup.drain() {
+            sys.run_now(res);
+        }
+    }
+
+    /// Abandons an interrupted `dispatch_budgeted` cycle.
+    fn clear_budgeted(&mut self) {
+        self.current_stage = 0;
+
+        for stage in &mut self.stages {
+            stage.clear_progress();
+        }
+    }
+
+    fn assert_not_shut_down(&self) {
+        assert!(!self.shut_down, "dispatcher has been shut down");
+    }
+}
+
+/// Describes the system which was run by [`Dispatcher::step`].
+///
+/// [`Dispatcher::step`]: struct.Dispatcher.html#method.step
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DispatchStep {
+    /// The name of the system; empty for unnamed
+    /// and thread local systems.
+    pub name: String,
+    /// The id of the system, or `None` for thread local systems.
+    pub id: Option<usize>,
+    /// Whether there are systems left to run.
+    pub pending: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct SystemId(pub usize);
 
-pub type SystemExecSend<'b> = Box<for<'a> RunNow<'a> + Send + 'b>;
-pub type ThreadLocal<'a> = SmallVec<[Box<for<'b> RunNow<'b> + 'a>; 4]>;
+pub type SystemExecSend<'b> = Box<dyn for<'a> RunNow<'a> + Send + 'b>;
+pub type ThreadLocal<'a> = SmallVec<[Box<dyn for<'b> RunNow<'b> + 'a>; 4]>;
+
+/// Creates a thread pool; if `name` is given, the worker threads
+/// are called `{name}-worker-{index}`. If `num_threads` is `None`,
+/// rayon picks the number of threads.
+#[cfg(not(target_os = "emscripten"))]
+pub fn create_thread_pool(
+    name: Option<&str>,
+    num_threads: Option<usize>,
+) -> ::std::sync::Arc<::rayon::ThreadPool> {
+    use std::sync::Arc;
+    use rayon::{Configuration, ThreadPool};
+
+    let mut config = Configuration::new();
+    if let Some(num_threads) = num_threads {
+        config = config.num_threads(num_threads);
+    }
+    if let Some(name) = name {
+        let name = name.to_owned();
+        config = config.thread_name(move |index| format!("{}-worker-{}", name, index));
+    }
+
+    Arc::new(ThreadPool::new(config).expect("Invalid thread pool configuration"))
+}
+
+/// Describes the thread pool of a dispatcher.
+#[cfg(not(target_os = "emscripten"))]
+#[derive(Default)]
+pub struct PoolConfig {
+    /// The name of the worker threads, see `create_thread_pool`.
+    pub name: Option<String>,
+    /// The number of worker threads; rayon picks it if `None`.
+    pub num_threads: Option<usize>,
+    /// Whether the pool was passed with `DispatcherBuilder::with_pool`,
+    /// so the dispatcher can't recreate it.
+    pub custom: bool,
+}
+
+#[cfg(not(target_os = "emscripten"))]
+impl PoolConfig {
+    pub fn create(&self) -> ::std::sync::Arc<::rayon::ThreadPool> {
+        create_thread_pool(self.name.as_deref(), self.num_threads)
+    }
+}
+
+/// Returns the thread pool, creating it if there's none yet.
+#[cfg(not(target_os = "emscripten"))]
+fn lazy_pool<'p>(
+    pool: &'p mut Option<::std::sync::Arc<::rayon::ThreadPool>>,
+    config: &PoolConfig,
+) -> &'p ::rayon::ThreadPool {
+    pool.get_or_insert_with(|| config.create())
+}
+
+/// Everything a `Dispatcher` is made of, apart from the thread pool.
+pub struct DispatcherParts<'a, 'b> {
+    pub stages: Vec<Stage<'a>>,
+    pub dependencies: Dependencies,
+    pub thread_local: ThreadLocal<'b>,
+    pub startup: ThreadLocal<'b>,
+    pub shutdown: ThreadLocal<'b>,
+    pub time_limit: Option<::std::time::Duration>,
+}
 
 #[cfg(not(target_os = "emscripten"))]
 pub fn new_dispatcher<'a, 'b>(
-    stages: Vec<Stage<'a>>,
-    thread_local: ThreadLocal<'b>,
-    thread_pool: ::std::sync::Arc<::rayon::ThreadPool>,
+    parts: DispatcherParts<'a, 'b>,
+    thread_pool: Option<::std::sync::Arc<::rayon::ThreadPool>>,
+    pool_config: PoolConfig,
 ) -> Dispatcher<'a, 'b> {
     Dispatcher {
-        stages,
-        thread_local,
+        stages: parts.stages,
+        dependencies: parts.dependencies,
+        thread_local: parts.thread_local,
+        startup: parts.startup,
+        shutdown: parts.shutdown,
+        shut_down: false,
+        next_step: 0,
+        current_stage: 0,
+        time_limit: parts.time_limit,
+        last_dispatch_duration: Default::default(),
         thread_pool,
+        pool_config,
     }
 }
 
 #[cfg(target_os = "emscripten")]
-pub fn new_dispatcher<'a, 'b>(
-    stages: Vec<Stage<'a>>,
-    thread_local: ThreadLocal<'b>,
-) -> Dispatcher<'a, 'b> {
+pub fn new_dispatcher<'a, 'b>(parts: DispatcherParts<'a, 'b>) -> Dispatcher<'a, 'b> {
     Dispatcher {
-        stages,
-        thread_local,
+        stages: parts.stages,
+        dependencies: parts.dependencies,
+        thread_local: parts.thread_local,
+        startup: parts.startup,
+        shutdown: parts.shutdown,
+        shut_down: false,
+        next_step: 0,
+        current_stage: 0,
+        time_limit: parts.time_limit,
+        last_dispatch_duration: Default::default(),
     }
 }
 
@@ -184,6 +683,71 @@ mod tests {
         d.dispatch(&mut new_resources());
     }
 
+    #[test]
+    fn collect_stats() {
+        let mut d = new_builder().build();
+
+        let stats = d.collect_stats(&new_resources());
+
+        assert_eq!(stats.per_system.len(), 6);
+        assert!(stats.max_parallelism >= 1);
+        let threads = d.thread_pool.as_ref().unwrap().current_num_threads();
+        assert!(stats.max_parallelism <= threads);
+        assert!(stats.scheduler_overhead <= stats.total_duration);
+    }
+
+    #[test]
+    fn num_systems_and_resources() {
+        struct Other;
+
+        struct ReadOther;
+
+        impl<'a> System<'a> for ReadOther {
+            type SystemData = (Fetch<'a, Res>, Fetch<'a, Other>);
+
+            fn run(&mut self, _: Self::SystemData) {}
+        }
+
+        let d = new_builder()
+            .with(ReadOther, "other", &[])
+            .with_thread_local(Dummy(10))
+            .build();
+
+        assert_eq!(d.num_systems(), 7);
+        assert_eq!(d.num_resources(), 2);
+    }
+
+    #[test]
+    fn no_default_pool() {
+        let mut d = new_builder().no_default_pool().build();
+        assert!(d.thread_pool.is_none());
+
+        d.dispatch(&new_resources());
+        assert!(d.thread_pool.is_some());
+    }
+
+    #[test]
+    fn max_concurrent() {
+        let mut d = new_builder().no_default_pool().build();
+        d.set_max_concurrent(2);
+        assert!(d.thread_pool.is_none());
+
+        d.dispatch(&new_resources());
+        assert_eq!(d.thread_pool.as_ref().unwrap().current_num_threads(), 2);
+
+        let d = new_builder().with_max_concurrent(3).build();
+        assert_eq!(d.thread_pool.as_ref().unwrap().current_num_threads(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't limit the number of threads of a pool passed with `with_pool`")]
+    fn max_concurrent_custom_pool() {
+        use dispatch::dispatcher::create_thread_pool;
+
+        let mut d = new_builder().with_pool(create_thread_pool(None, Some(2))).build();
+        d.set_max_concurrent(1);
+    }
+
     #[test]
     fn stages_async() {
         let mut d = new_builder().build_async(new_resources());
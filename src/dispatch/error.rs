@@ -0,0 +1,184 @@
+use std::error::Error;
+use std::fmt::{Display, Error as FormatError, Formatter};
+use std::panic::Location;
+
+use res::ResourceId;
+
+/// Decides how a [`DispatcherBuilder`] reacts to
+/// invalid registrations.
+///
+/// [`DispatcherBuilder`]: struct.DispatcherBuilder.html
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorStrategy {
+    /// Panic as soon as an error is detected (the default).
+    Panic,
+    /// Carry on building and collect all errors, which are
+    /// then returned from `try_build` (or make `build` panic).
+    Collect,
+}
+
+/// An error detected while building a `Dispatcher`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BuildError {
+    /// A system with the same name has already been registered.
+    DuplicateName(String),
+    /// There's no system registered with this name.
+    UnknownSystem(String),
+    /// A system depends on another one which was added
+    /// after a barrier following it.
+    DependencyAfterBarrier {
+        /// The depending system.
+        system: String,
+        /// The system it depends on.
+        dependency: String,
+    },
+    /// The dependencies between these systems form a cycle.
+    Cycle(Vec<String>),
+    /// A system lists the same resource in both its reads and writes.
+    ReadAndWrite {
+        /// The name of the system.
+        system: String,
+        /// The resource which is both read and written.
+        resource: ResourceId,
+    },
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FormatError> {
+        match *self {
+            BuildError::DuplicateName(ref name) => write!(
+                f,
+                "Cannot insert multiple systems with the same name (\"{}\")",
+                name
+            ),
+            BuildError::UnknownSystem(ref name) => {
+                write!(f, "No such system registered (\"{}\")", name)
+            }
+            BuildError::DependencyAfterBarrier {
+                ref system,
+                ref dependency,
+            } => write!(
+                f,
+                "System \"{}\" depends on \"{}\", which was added after a barrier",
+                system, dependency
+            ),
+            BuildError::Cycle(ref systems) => {
+                write!(f, "Cyclic dependency between the systems {:?}", systems)
+            }
+            BuildError::ReadAndWrite {
+                ref system,
+                ref resource,
+            } => write!(
+                f,
+                "System \"{}\" lists {:?} in both reads and writes",
+                system, resource
+            ),
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn description(&self) -> &str {
+        match *self {
+            BuildError::DuplicateName(_) => "Cannot insert multiple systems with the same name",
+            BuildError::UnknownSystem(_) => "No such system registered",
+            BuildError::DependencyAfterBarrier { .. } => {
+                "Dependency on a system which was added after a barrier"
+            }
+            BuildError::Cycle(_) => "Cyclic dependency between systems",
+            BuildError::ReadAndWrite { .. } => "Resource listed in both reads and writes",
+        }
+    }
+}
+
+/// An error which occurred while dispatching,
+/// returned by [`Dispatcher::try_dispatch`].
+///
+/// The panic message is also available as the `source` of the error.
+///
+/// [`Dispatcher::try_dispatch`]: struct.Dispatcher.html#method.try_dispatch
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DispatchError {
+    /// A system panicked.
+    SystemPanic {
+        /// The id of the system.
+        id: usize,
+        /// The name of the system; empty for unnamed systems.
+        name: String,
+        /// Where the system was added to the `DispatcherBuilder`.
+        location: &'static Location<'static>,
+        /// The panic message.
+        message: PanicMessage,
+    },
+    /// A thread local system panicked.
+    ThreadLocalPanic {
+        /// The index of the system, counting thread
+        /// local systems in the order they were added in.
+        index: usize,
+        /// The panic message.
+        message: PanicMessage,
+    },
+}
+
+impl Display for DispatchError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FormatError> {
+        match *self {
+            DispatchError::SystemPanic {
+                id,
+                ref name,
+                location,
+                ref message,
+            } => write!(
+                f,
+                "System \"{}\" (id={}, registered at {}) panicked: {}",
+                name, id, location, message
+            ),
+            DispatchError::ThreadLocalPanic { index, ref message } => write!(
+                f,
+                "Thread local system (index={}) panicked: {}",
+                index, message
+            ),
+        }
+    }
+}
+
+impl Error for DispatchError {
+    fn description(&self) -> &str {
+        match *self {
+            DispatchError::SystemPanic { .. } => "A system panicked",
+            DispatchError::ThreadLocalPanic { .. } => "A thread local system panicked",
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DispatchError::SystemPanic { ref message, .. } |
+            DispatchError::ThreadLocalPanic { ref message, .. } => Some(message),
+        }
+    }
+}
+
+/// The message of a panic, extracted from its payload.
+///
+/// If the payload was neither a `&str` nor a `String`,
+/// the message is `"Box<Any>"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PanicMessage(pub String);
+
+impl<'a> From<&'a str> for PanicMessage {
+    fn from(message: &'a str) -> Self {
+        PanicMessage(message.to_owned())
+    }
+}
+
+impl Display for PanicMessage {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FormatError> {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for PanicMessage {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
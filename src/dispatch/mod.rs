@@ -1,15 +1,29 @@
 #[cfg(not(target_os = "emscripten"))]
 pub use self::async::AsyncDispatcher;
-pub use self::builder::DispatcherBuilder;
-pub use self::dispatcher::Dispatcher;
+pub use self::benchmark::BenchmarkReport;
+pub use self::budget::{DispatchOutcome, ExecutionBudget};
+pub use self::builder::{DispatcherBuilder, RegisteredSystem};
+pub use self::cancel::{CancellationToken, Cancelled};
+pub use self::dependencies::Dependencies;
+pub use self::dispatcher::{DispatchStep, Dispatcher};
+pub use self::error::{BuildError, DispatchError, ErrorStrategy, PanicMessage};
 #[cfg(not(target_os = "emscripten"))]
 pub use self::par_seq::{Par, ParSeq, Seq};
+#[cfg(not(target_os = "emscripten"))]
+pub use self::stats::DispatchStats;
 
 #[cfg(not(target_os = "emscripten"))]
 mod async;
+mod benchmark;
+mod budget;
 mod builder;
+mod cancel;
+mod dependencies;
 mod dispatcher;
+mod error;
 #[cfg(not(target_os = "emscripten"))]
 mod par_seq;
 mod stage;
+#[cfg(not(target_os = "emscripten"))]
+mod stats;
 mod util;
@@ -30,10 +30,19 @@
 //!   in code).
 //!
 
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
 use arrayvec::ArrayVec;
 use smallvec::SmallVec;
 
+use dispatch::cancel::CancellationToken;
 use dispatch::dispatcher::{SystemExecSend, SystemId};
+use dispatch::error::DispatchError;
+#[cfg(not(target_os = "emscripten"))]
+use dispatch::stats::StatsRecorder;
 use dispatch::util::check_intersection;
 use res::{ResourceId, Resources};
 use system::{RunningTime, System};
@@ -66,9 +75,106 @@ enum InsertionTarget {
     NewStage,
 }
 
+/// Everything the `StagesBuilder` needs to know to insert a system.
+pub struct SystemEntry<'a> {
+    pub id: SystemId,
+    pub name: String,
+    pub dependencies: SmallVec<[SystemId; 4]>,
+    pub reads: Vec<ResourceId>,
+    pub writes: Vec<ResourceId>,
+    pub running_time: RunningTime,
+    pub location: &'static Location<'static>,
+    pub system: SystemExecSend<'a>,
+}
+
+impl<'a> SystemEntry<'a> {
+    /// Creates an entry for a typed system, taking
+    /// its reads and writes from its `SystemData`.
+    pub fn new<T>(
+        system: T,
+        id: SystemId,
+        name: &str,
+        dependencies: SmallVec<[SystemId; 4]>,
+        location: &'static Location<'static>,
+    ) -> Self
+    where
+        T: for<'b> System<'b> + Send + 'a,
+    {
+        use system::SystemData;
+
+        SystemEntry {
+            id,
+            name: name.to_owned(),
+            dependencies,
+            reads: T::SystemData::reads(0),
+            writes: T::SystemData::writes(0),
+            running_time: system.running_time(),
+            location,
+            system: Box::new(system),
+        }
+    }
+}
+
+/// A system together with its id, name and the location it was registered at.
+pub struct SystemExec<'a> {
+    system: SystemExecSend<'a>,
+    id: SystemId,
+    name: String,
+    location: &'static Location<'static>,
+}
+
+impl<'a> SystemExec<'a> {
+    fn run_now(&mut self, res: &Resources) {
+        self.system.run_now(res);
+    }
+
+    #[cfg(not(target_os = "emscripten"))]
+    fn run_recorded(&mut self, res: &Resources, recorder: &StatsRecorder) {
+        let SystemExec {
+            ref mut system,
+            ref name,
+            ..
+        } = *self;
+
+        recorder.record(name, || system.run_now(res));
+    }
+
+    /// Runs the system, returning an error instead of
+    /// panicking if the system panics.
+    fn try_run(&mut self, res: &Resources) -> Result<(), DispatchError> {
+        let system = &mut self.system;
+
+        panic::catch_unwind(AssertUnwindSafe(|| system.run_now(res))).map_err(|payload| {
+            DispatchError::SystemPanic {
+                id: self.id.0,
+                name: self.name.clone(),
+                location: self.location,
+                message: panic_message(&*payload).into(),
+            }
+        })
+    }
+
+    fn reset(&mut self) {
+        self.system.reset_now();
+    }
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "Box<Any>"
+    }
+}
+
 #[derive(Default)]
 pub struct Stage<'a> {
-    groups: GroupVec<ArrayVec<[SystemExecSend<'a>; MAX_SYSTEMS_PER_GROUP]>>,
+    groups: GroupVec<ArrayVec<[SystemExec<'a>; MAX_SYSTEMS_PER_GROUP]>>,
+    /// The number of systems of every group which
+    /// already ran in an interrupted `execute_until`.
+    progress: GroupVec<usize>,
 }
 
 impl<'a> Stage<'a> {
@@ -87,6 +193,18 @@ impl<'a> Stage<'a> {
             });
     }
 
+    /// Like `execute`, but records the running time of every system.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn execute_recorded(&mut self, res: &Resources, recorder: &StatsRecorder) {
+        use rayon::prelude::*;
+
+        self.groups
+            .par_iter_mut()
+            .for_each(|group| for system in group {
+                system.run_recorded(res, recorder);
+            });
+    }
+
     pub fn execute_seq(&mut self, res: &Resources) {
         for group in &mut self.groups {
             for system in group {
@@ -94,6 +212,197 @@ impl<'a> Stage<'a> {
             }
         }
     }
+
+    /// Returns the number of systems in this stage.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|group| group.len()).sum()
+    }
+
+    /// Runs only the `index`th system of this stage, counting
+    /// group by group. Returns the id and name of the system.
+    pub fn run_single(&mut self, index: usize, res: &Resources) -> Option<(SystemId, &str)> {
+        let system = self.groups.iter_mut().flat_map(|group| group.iter_mut()).nth(index)?;
+        system.run_now(res);
+
+        Some((system.id, &system.name))
+    }
+
+    pub fn reset(&mut self) {
+        for group in &mut self.groups {
+            for system in group {
+                system.reset();
+            }
+        }
+    }
+
+    /// Like `execute`, but doesn't start any more systems after
+    /// `deadline` (if any) once `progressed` is set (which happens as soon
+    /// as a system ran). Systems which ran by an earlier, interrupted
+    /// call are skipped. The ids of the systems which ran are
+    /// appended to `completed`.
+    ///
+    /// Returns `true` if all systems of this stage ran.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn execute_until(
+        &mut self,
+        res: &Resources,
+        deadline: Option<Instant>,
+        progressed: &AtomicBool,
+        completed: &mut Vec<usize>,
+    ) -> bool {
+        use rayon::prelude::*;
+
+        let ran: Vec<Vec<usize>> = self.groups
+            .par_iter_mut()
+            .zip(self.progress.par_iter_mut())
+            .map(|(group, progress)| run_group_until(group, progress, res, deadline, progressed))
+            .collect();
+        completed.extend(ran.into_iter().flatten());
+
+        self.finish_progress()
+    }
+
+    /// Like `execute_seq`, but stops after `deadline`;
+    /// see the multithreaded version for details.
+    #[cfg(target_os = "emscripten")]
+    pub fn execute_until(
+        &mut self,
+        res: &Resources,
+        deadline: Option<Instant>,
+        progressed: &AtomicBool,
+        completed: &mut Vec<usize>,
+    ) -> bool {
+        for (group, progress) in self.groups.iter_mut().zip(self.progress.iter_mut()) {
+            completed.extend(run_group_until(group, progress, res, deadline, progressed));
+        }
+
+        self.finish_progress()
+    }
+
+    /// Appends the ids of the systems which haven't run yet to `pending`.
+    pub fn pending(&self, pending: &mut Vec<usize>) {
+        for (group, &progress) in self.groups.iter().zip(self.progress.iter()) {
+            pending.extend(group[progress..].iter().map(|system| system.id.0));
+        }
+    }
+
+    /// Resets the progress if all systems ran.
+    fn finish_progress(&mut self) -> bool {
+        let finished = self.groups
+            .iter()
+            .zip(self.progress.iter())
+            .all(|(group, &progress)| progress == group.len());
+
+        if finished {
+            self.clear_progress();
+        }
+
+        finished
+    }
+
+    /// Forgets about the systems which ran by an interrupted
+    /// `execute_until`, so the next call starts over.
+    pub fn clear_progress(&mut self) {
+        for progress in &mut self.progress {
+            *progress = 0;
+        }
+    }
+
+    /// Like `execute`, but returns an error if a system panics.
+    /// The remaining systems of its group are skipped then.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn try_execute(&mut self, res: &Resources) -> Result<(), DispatchError> {
+        use rayon::prelude::*;
+
+        self.groups
+            .par_iter_mut()
+            .map(|group| try_run_group(group, res))
+            .reduce(|| Ok(()), Result::and)
+    }
+
+    /// Like `execute_seq`, but returns an error if a system panics.
+    #[cfg(target_os = "emscripten")]
+    pub fn try_execute(&mut self, res: &Resources) -> Result<(), DispatchError> {
+        for group in &mut self.groups {
+            try_run_group(group, res)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like `execute`, but doesn't start any more systems once
+    /// `token` got cancelled. Returns the number of systems which were run.
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn execute_cancellable(&mut self, res: &Resources, token: &CancellationToken) -> usize {
+        use rayon::prelude::*;
+
+        self.groups
+            .par_iter_mut()
+            .map(|group| run_group_cancellable(group, res, token))
+            .sum()
+    }
+
+    /// Like `execute_seq`, but doesn't start any more systems once
+    /// `token` got cancelled. Returns the number of systems which were run.
+    #[cfg(target_os = "emscripten")]
+    pub fn execute_cancellable(&mut self, res: &Resources, token: &CancellationToken) -> usize {
+        self.groups
+            .iter_mut()
+            .map(|group| run_group_cancellable(group, res, token))
+            .sum()
+    }
+}
+
+fn run_group_until(
+    group: &mut [SystemExec],
+    progress: &mut usize,
+    res: &Resources,
+    deadline: Option<Instant>,
+    progressed: &AtomicBool,
+) -> Vec<usize> {
+    let mut ran = Vec::new();
+
+    for system in &mut group[*progress..] {
+        if let Some(deadline) = deadline {
+            if progressed.load(Ordering::SeqCst) && Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        system.run_now(res);
+        progressed.store(true, Ordering::SeqCst);
+        ran.push(system.id.0);
+        *progress += 1;
+    }
+
+    ran
+}
+
+fn try_run_group(group: &mut [SystemExec], res: &Resources) -> Result<(), DispatchError> {
+    for system in group {
+        system.try_run(res)?;
+    }
+
+    Ok(())
+}
+
+fn run_group_cancellable(
+    group: &mut [SystemExec],
+    res: &Resources,
+    token: &CancellationToken,
+) -> usize {
+    let mut ran = 0;
+
+    for system in group {
+        if token.is_cancelled() {
+            break;
+        }
+
+        system.run_now(res);
+        ran += 1;
+    }
+
+    ran
 }
 
 #[derive(Default)]
@@ -111,21 +420,22 @@ impl<'a> StagesBuilder<'a> {
         self.barrier = self.stages.len();
     }
 
-    pub fn insert<T>(&mut self, mut dep: SmallVec<[SystemId; 4]>, id: SystemId, system: T)
-    where
-        T: for<'b> System<'b> + Send + 'a,
-    {
-        use system::SystemData;
-
-        let mut reads = T::SystemData::reads(0);
-        let writes = T::SystemData::writes(0);
+    pub fn insert(&mut self, entry: SystemEntry<'a>) {
+        let SystemEntry {
+            id,
+            name,
+            mut dependencies,
+            mut reads,
+            writes,
+            running_time,
+            location,
+            system,
+        } = entry;
 
         reads.sort();
         reads.dedup();
 
-        let new_time = system.running_time();
-
-        let target = self.insertion_target(&reads, &writes, &mut dep, new_time);
+        let target = self.insertion_target(&reads, &writes, &mut dependencies, running_time);
 
         let (stage, group) = match target {
             InsertionTarget::Stage(stage) => {
@@ -147,8 +457,13 @@ impl<'a> StagesBuilder<'a> {
 
         self.ids[stage][group].push(id);
         self.reads[stage][group].extend(reads);
-        self.running_time[stage][group] += new_time as u8;
-        self.stages[stage].groups[group].push(Box::new(system));
+        self.running_time[stage][group] += running_time as u8;
+        self.stages[stage].groups[group].push(SystemExec {
+            system,
+            id,
+            name,
+            location,
+        });
         self.writes[stage][group].extend(writes);
     }
 
@@ -169,6 +484,7 @@ impl<'a> StagesBuilder<'a> {
         self.reads[stage].push(SmallVec::new());
         self.running_time[stage].push(0);
         self.stages[stage].groups.push(ArrayVec::new());
+        self.stages[stage].progress.push(0);
         self.writes[stage].push(SmallVec::new());
     }
 
@@ -456,9 +772,11 @@ mod tests {
 
         let mut builder: StagesBuilder = Default::default();
 
-        builder.insert(SmallVec::new(), SystemId(0), SysA);
-        builder.insert(SmallVec::new(), SystemId(1), SysB);
-        builder.insert(SmallVec::new(), SystemId(2), SysC);
+        let location = Location::caller();
+
+        builder.insert(SystemEntry::new(SysA, SystemId(0), "a", SmallVec::new(), location));
+        builder.insert(SystemEntry::new(SysB, SystemId(1), "b", SmallVec::new(), location));
+        builder.insert(SystemEntry::new(SysC, SystemId(2), "c", SmallVec::new(), location));
 
         let ref ids = builder.ids[0];
 
@@ -479,9 +797,14 @@ mod tests {
             fn run(&mut self, _: Self::SystemData) {}
         }
 
-        builder.insert(SmallVec::from(&[][..]), SystemId(0), Sys);
-        builder.insert(SmallVec::from(&[SystemId(0)][..]), SystemId(1), Sys);
-        builder.insert(SmallVec::from(&[SystemId(1)][..]), SystemId(2), Sys);
+        let location = Location::caller();
+
+        let dep_0 = SmallVec::from(&[SystemId(0)][..]);
+        let dep_1 = SmallVec::from(&[SystemId(1)][..]);
+
+        builder.insert(SystemEntry::new(Sys, SystemId(0), "0", SmallVec::new(), location));
+        builder.insert(SystemEntry::new(Sys, SystemId(1), "1", dep_0, location));
+        builder.insert(SystemEntry::new(Sys, SystemId(2), "2", dep_1, location));
 
         assert_eq!(builder.ids[0][0][0], SystemId(0));
         assert_eq!(builder.ids[1][0][0], SystemId(1));
@@ -0,0 +1,91 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Statistics about a single dispatch, returned
+/// by [`Dispatcher::collect_stats`].
+///
+/// [`Dispatcher::collect_stats`]: struct.Dispatcher.html#method.collect_stats
+#[derive(Clone, Debug)]
+pub struct DispatchStats {
+    /// The time it took to execute all the (not thread local) systems.
+    pub total_duration: Duration,
+    /// The name and running time of every system, in the order they finished.
+    /// Systems registered without a name are listed with `""`.
+    pub per_system: Vec<(String, Duration)>,
+    /// The maximum number of systems which were running at the same time.
+    pub max_parallelism: usize,
+    /// The time during which no system was running,
+    /// spent in the dispatcher itself.
+    pub scheduler_overhead: Duration,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    per_system: Vec<(String, Duration)>,
+    running: usize,
+    max_running: usize,
+    busy: Duration,
+    busy_since: Option<Instant>,
+}
+
+/// Collects the running times of the systems while dispatching.
+pub struct StatsRecorder {
+    start: Instant,
+    state: Mutex<RecorderState>,
+}
+
+impl StatsRecorder {
+    pub fn new() -> Self {
+        StatsRecorder {
+            start: Instant::now(),
+            state: Default::default(),
+        }
+    }
+
+    /// Runs `f`, recording its running time for the system `name`.
+    pub fn record<F>(&self, name: &str, f: F)
+    where
+        F: FnOnce(),
+    {
+        self.started();
+        let start = Instant::now();
+        f();
+        self.finished(name, start.elapsed());
+    }
+
+    pub fn finish(self) -> DispatchStats {
+        let total_duration = self.start.elapsed();
+        let state = self.state.into_inner().expect("Mutex poisoned");
+
+        DispatchStats {
+            total_duration,
+            per_system: state.per_system,
+            max_parallelism: state.max_running,
+            scheduler_overhead: total_duration - state.busy.min(total_duration),
+        }
+    }
+
+    fn started(&self) {
+        let mut state = self.state.lock().expect("Mutex poisoned");
+
+        if state.running == 0 {
+            state.busy_since = Some(Instant::now());
+        }
+
+        state.running += 1;
+        state.max_running = state.max_running.max(state.running);
+    }
+
+    fn finished(&self, name: &str, duration: Duration) {
+        let mut state = self.state.lock().expect("Mutex poisoned");
+
+        state.running -= 1;
+        state.per_system.push((name.to_owned(), duration));
+
+        if state.running == 0 {
+            if let Some(since) = state.busy_since.take() {
+                state.busy += since.elapsed();
+            }
+        }
+    }
+}
@@ -59,6 +59,9 @@
 
 extern crate arrayvec;
 extern crate fnv;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
 #[macro_use]
 extern crate mopa;
 #[cfg(not(target_os = "emscripten"))]
@@ -72,10 +75,15 @@ mod dispatch;
 mod res;
 mod system;
 
-pub use dispatch::{Dispatcher, DispatcherBuilder};
+pub use dispatch::{BenchmarkReport, BuildError, CancellationToken, Cancelled, Dependencies, DispatchError,
+                   DispatchOutcome, DispatchStep, Dispatcher, DispatcherBuilder, ErrorStrategy,
+                   ExecutionBudget, PanicMessage, RegisteredSystem};
 #[cfg(not(target_os = "emscripten"))]
 pub use dispatch::{Par, ParSeq, Seq};
 #[cfg(not(target_os = "emscripten"))]
 pub use dispatch::AsyncDispatcher;
-pub use res::{Fetch, FetchId, FetchIdMut, FetchMut, Resource, ResourceId, Resources};
+#[cfg(not(target_os = "emscripten"))]
+pub use dispatch::DispatchStats;
+pub use res::{Fetch, FetchId, FetchIdMut, FetchMut, Resource, ResourceId, Resources, ResourcesIntoIter,
+              ResourcesIter, ScopedResources, Snapshot};
 pub use system::{RunNow, RunningTime, System, SystemData};
@@ -2,7 +2,7 @@ use std::collections::hash_map::Entry as StdEntry;
 use std::marker::PhantomData;
 
 use cell::TrustCell;
-use res::{FetchMut, Resource, ResourceId};
+use res::{FetchMut, Listener, Resource, ResourceId, WriteBorrow};
 
 /// An entry to a resource of the `Resources` struct.
 /// This is similar to the Entry API found in the standard library.
@@ -22,6 +22,7 @@ use res::{FetchMut, Resource, ResourceId};
 /// ```
 pub struct Entry<'a, T: 'a> {
     inner: StdEntry<'a, ResourceId, TrustCell<Box<Resource>>>,
+    listeners: &'a [Listener],
     marker: PhantomData<T>,
 }
 
@@ -44,7 +45,7 @@ where
     {
         let value = self.inner
             .or_insert_with(move || TrustCell::new(Box::new(f())));
-        let inner = value.borrow_mut();
+        let inner = WriteBorrow::new(value.borrow_mut(), self.listeners);
 
         FetchMut {
             inner,
@@ -53,9 +54,13 @@ where
     }
 }
 
-pub fn create_entry<'a, T>(e: StdEntry<'a, ResourceId, TrustCell<Box<Resource>>>) -> Entry<'a, T> {
+pub fn create_entry<'a, T>(
+    e: StdEntry<'a, ResourceId, TrustCell<Box<dyn Resource>>>,
+    listeners: &'a [Listener],
+) -> Entry<'a, T> {
     Entry {
         inner: e,
+        listeners,
         marker: PhantomData,
     }
 }
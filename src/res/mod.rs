@@ -1,22 +1,37 @@
 //! Module for resource related types
 
 pub use self::entry::Entry;
+pub use self::scope::ScopedResources;
 
 use std::any::TypeId;
+use std::fmt;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use mopa::Any;
 
 use self::entry::create_entry;
+use self::scope::create_scope;
 use cell::{Ref, RefMut, TrustCell};
 use system::SystemData;
 
 mod entry;
+mod scope;
 
 const RESOURCE_NOT_FOUND: &str = "No resource with the given id";
 
+/// A callback registered with [`Resources::watch`].
+///
+/// [`Resources::watch`]: struct.Resources.html#method.watch
+type Listener = Box<dyn Fn(&dyn Resource) + Send + Sync>;
+
+/// Clones a type-erased resource; registered by
+/// [`Resources::register_clone_factory`].
+///
+/// [`Resources::register_clone_factory`]: struct.Resources.html#method.register_clone_factory
+type Cloner = fn(&dyn Resource) -> Box<dyn Resource>;
+
 /// Return value of [`Resources::fetch`].
 ///
 /// [`Resources::fetch`]: struct.Resources.html#method.fetch
@@ -72,20 +87,20 @@ impl<'a> Deref for FetchId<'a> {
 ///
 /// [`Resources::fetch_id_mut`]: struct.Resources.html#method.fetch_id_mut
 pub struct FetchIdMut<'a> {
-    inner: RefMut<'a, Box<Resource>>,
+    inner: WriteBorrow<'a>,
 }
 
 impl<'a> Deref for FetchIdMut<'a> {
     type Target = Resource;
 
     fn deref(&self) -> &Resource {
-        self.inner.as_ref()
+        self.inner.value.as_ref()
     }
 }
 
 impl<'a> DerefMut for FetchIdMut<'a> {
     fn deref_mut(&mut self) -> &mut Resource {
-        self.inner.as_mut()
+        self.inner.value.as_mut()
     }
 }
 
@@ -93,7 +108,7 @@ impl<'a> DerefMut for FetchIdMut<'a> {
 ///
 /// [`Resources::fetch_mut`]: struct.Resources.html#method.fetch_mut
 pub struct FetchMut<'a, T: 'a> {
-    inner: RefMut<'a, Box<Resource>>,
+    inner: WriteBorrow<'a>,
     phantom: PhantomData<&'a mut T>,
 }
 
@@ -104,7 +119,7 @@ where
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { self.inner.downcast_ref_unchecked() }
+        unsafe { self.inner.value.downcast_ref_unchecked() }
     }
 }
 
@@ -113,7 +128,35 @@ where
     T: Resource,
 {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { self.inner.downcast_mut_unchecked() }
+        unsafe { self.inner.value.downcast_mut_unchecked() }
+    }
+}
+
+/// A mutable borrow of a resource which calls the
+/// listeners of the resource once it gets released.
+///
+/// Listeners aren't called if the borrow is released
+/// because of a panic.
+struct WriteBorrow<'a> {
+    value: RefMut<'a, Box<dyn Resource>>,
+    listeners: &'a [Listener],
+}
+
+impl<'a> WriteBorrow<'a> {
+    fn new(value: RefMut<'a, Box<dyn Resource>>, listeners: &'a [Listener]) -> Self {
+        WriteBorrow { value, listeners }
+    }
+}
+
+impl<'a> Drop for WriteBorrow<'a> {
+    fn drop(&mut self) {
+        if ::std::thread::panicking() {
+            return;
+        }
+
+        for listener in self.listeners {
+            listener(self.value.as_ref());
+        }
     }
 }
 
@@ -219,7 +262,11 @@ impl ResourceId {
 /// are convenience methods which assume this id is `0`.
 #[derive(Default)]
 pub struct Resources {
+    cloners: FnvHashMap<TypeId, Cloner>,
+    listeners: FnvHashMap<ResourceId, Vec<Listener>>,
     resources: FnvHashMap<ResourceId, TrustCell<Box<Resource>>>,
+    /// The names of the resource types, used by the `Debug` implementation.
+    type_names: FnvHashMap<TypeId, &'static str>,
 }
 
 impl Resources {
@@ -273,6 +320,10 @@ impl Resources {
     {
         use std::collections::hash_map::Entry;
 
+        #[cfg(feature = "log")]
+        debug!("Resources: add {:?}", ::std::any::type_name::<R>());
+
+        self.record_type_name::<R>();
         let entry = self.resources.entry(ResourceId::new_with_id::<R>(id));
 
         if let Entry::Vacant(e) = entry {
@@ -282,6 +333,274 @@ impl Resources {
         }
     }
 
+    /// Replaces the resource with type `R` and id 0 with `r`, returning
+    /// the old value. If there is no such resource, `r` is added and
+    /// `None` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use shred::Resources;
+    ///
+    /// let mut res = Resources::new();
+    ///
+    /// assert_eq!(res.replace(vec![1u32]), None);
+    /// assert_eq!(res.replace(vec![2u32]), Some(vec![1]));
+    /// assert_eq!(*res.fetch::<Vec<u32>>(0), vec![2]);
+    /// ```
+    pub fn replace<R>(&mut self, r: R) -> Option<R>
+    where
+        R: Resource,
+    {
+        use std::collections::hash_map::Entry;
+        use std::mem;
+
+        self.record_type_name::<R>();
+        match self.resources.entry(ResourceId::new::<R>()) {
+            Entry::Occupied(mut e) => {
+                let old = e.get_mut()
+                    .get_mut()
+                    .downcast_mut::<R>()
+                    .expect("Resource stored with the id of another type");
+
+                Some(mem::replace(old, r))
+            }
+            Entry::Vacant(e) => {
+                e.insert(TrustCell::new(Box::new(r)));
+
+                None
+            }
+        }
+    }
+
+    /// Moves all resources of `other` into `self`. If both contain a
+    /// resource with the same id, the one of `other` is kept.
+    ///
+    /// Listeners and clone factories of both containers are kept as well.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use shred::Resources;
+    ///
+    /// let mut base = Resources::new();
+    /// base.add(1u32);
+    /// base.add(2u64);
+    ///
+    /// let mut level = Resources::new();
+    /// level.add(3u32);
+    ///
+    /// base.merge(level);
+    /// assert_eq!(*base.fetch::<u32>(0), 3);
+    /// assert_eq!(*base.fetch::<u64>(0), 2);
+    /// ```
+    pub fn merge(&mut self, other: Resources) {
+        self.cloners.extend(other.cloners);
+        for (id, listeners) in other.listeners {
+            self.listeners.entry(id).or_default().extend(listeners);
+        }
+        self.resources.extend(other.resources);
+        self.type_names.extend(other.type_names);
+    }
+
+    /// Like `add()`, but also registers a clone factory for `R`
+    /// (see `register_clone_factory()`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is already registered.
+    pub fn add_clone<R>(&mut self, r: R)
+    where
+        R: Resource + Clone,
+    {
+        self.add_clone_with_id(r, 0)
+    }
+
+    /// Like `add_clone()`, but allows specifying
+    /// an id while `add_clone()` assumes `0`.
+    pub fn add_clone_with_id<R>(&mut self, r: R, id: usize)
+    where
+        R: Resource + Clone,
+    {
+        self.add_with_id(r, id);
+        self.register_clone_factory::<R>();
+    }
+
+    /// Remembers how to clone resources of type `T`, so they are
+    /// included in the results of `try_clone()` and `snapshot()`.
+    /// This applies to resources of type `T` with any id, including
+    /// ones added before or after calling this.
+    pub fn register_clone_factory<T>(&mut self)
+    where
+        T: Resource + Clone,
+    {
+        self.cloners.insert(TypeId::of::<T>(), clone_resource::<T>);
+    }
+
+    /// Clones all the resources which have a clone factory
+    /// registered (see `register_clone_factory()`) into a new container.
+    ///
+    /// Returns the new container together with the (sorted) ids
+    /// of the resources which couldn't be cloned. Listeners
+    /// registered with `watch()` are not carried over.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the clonable resources is borrowed mutably.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::{ResourceId, Resources};
+    ///
+    /// struct NotClone;
+    ///
+    /// let mut res = Resources::new();
+    /// res.add_clone(5u32);
+    /// res.add(NotClone);
+    ///
+    /// let (copy, excluded) = res.try_clone();
+    ///
+    /// assert_eq!(*copy.fetch::<u32>(0), 5);
+    /// assert_eq!(excluded, vec![ResourceId::new::<NotClone>()]);
+    /// ```
+    pub fn try_clone(&self) -> (Resources, Vec<ResourceId>) {
+        let mut cloned = Resources::new();
+        cloned.cloners = self.cloners.clone();
+        cloned.type_names = self.type_names.clone();
+        let mut excluded = Vec::new();
+
+        for (&id, cell) in &self.resources {
+            match self.cloners.get(&id.0) {
+                Some(&cloner) => {
+                    let r = cloner(cell.borrow().as_ref());
+                    cloned.resources.insert(id, TrustCell::new(r));
+                }
+                None => excluded.push(id),
+            }
+        }
+
+        excluded.sort();
+
+        (cloned, excluded)
+    }
+
+    /// Takes a snapshot of all the resources which have a clone
+    /// factory registered; other resources are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the clonable resources is borrowed mutably.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(5u32);
+    /// res.register_clone_factory::<u32>();
+    ///
+    /// let snapshot = res.snapshot();
+    /// *res.fetch_mut::<u32>(0) = 7;
+    /// res.restore(snapshot);
+    ///
+    /// assert_eq!(*res.fetch::<u32>(0), 5);
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.try_clone().0)
+    }
+
+    /// Puts back the resources of a snapshot taken with `snapshot()`,
+    /// replacing their current values (or adding them again).
+    /// Resources which are not part of the snapshot stay unchanged.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.resources.extend((snapshot.0).resources);
+    }
+
+    /// Runs `f`, undoing its changes to clonable resources
+    /// if it returns an error or panics.
+    ///
+    /// Before running `f`, a `snapshot()` is taken. If `f` fails, these
+    /// clones are put back, and resources, listeners and clone factories
+    /// added by `f` are removed again; panics are resumed afterwards.
+    ///
+    /// Note that this is only atomic if all resources have a clone
+    /// factory: changes to resources which can't be cloned can't be
+    /// undone and are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// let mut res = Resources::new();
+    /// res.add_clone(10i32);
+    ///
+    /// let result: Result<(), &str> = res.transaction(|res| {
+    ///     let mut balance = res.fetch_mut::<i32>(0);
+    ///     *balance -= 20;
+    ///
+    ///     if *balance < 0 {
+    ///         return Err("not enough money");
+    ///     }
+    ///
+    ///     Ok(())
+    /// });
+    ///
+    /// assert!(result.is_err());
+    /// assert_eq!(*res.fetch::<i32>(0), 10);
+    /// ```
+    pub fn transaction<F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&mut Resources) -> Result<R, E>,
+    {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let checkpoint = Checkpoint {
+            ids: self.resources.keys().cloned().collect(),
+            cloners: self.cloners.clone(),
+            listeners: self.listeners
+                .iter()
+                .map(|(&id, listeners)| (id, listeners.len()))
+                .collect(),
+            snapshot: self.snapshot(),
+        };
+
+        match panic::catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(Ok(r)) => Ok(r),
+            Ok(Err(e)) => {
+                self.rollback(checkpoint);
+
+                Err(e)
+            }
+            Err(payload) => {
+                self.rollback(checkpoint);
+
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    /// Removes the resources and listeners which were added after
+    /// `checkpoint` was created and restores the others from its snapshot.
+    fn rollback(&mut self, checkpoint: Checkpoint) {
+        let Checkpoint {
+            ids,
+            cloners,
+            listeners,
+            snapshot,
+        } = checkpoint;
+
+        self.resources.retain(|id, _| ids.contains(id));
+        self.cloners = cloners;
+        self.listeners.retain(|id, _| listeners.contains_key(id));
+        for (id, list) in &mut self.listeners {
+            list.truncate(listeners[id]);
+        }
+        self.restore(snapshot);
+    }
+
     /// Returns true if the specified type / id combination
     /// is registered.
     pub fn has_value(&self, res_id: ResourceId) -> bool {
@@ -293,7 +612,82 @@ impl Resources {
     where
         R: Resource,
     {
-        create_entry(self.resources.entry(ResourceId::new::<R>()))
+        self.record_type_name::<R>();
+        let id = ResourceId::new::<R>();
+        let listeners = self.listeners.get(&id).map(|l| &l[..]).unwrap_or(&[]);
+
+        create_entry(self.resources.entry(id), listeners)
+    }
+
+    /// Returns the resource with type `T` and id 0 for reading,
+    /// adding `value` first if there is no such resource.
+    ///
+    /// Unlike `entry().or_insert()`, this returns a `Fetch`,
+    /// so it doesn't trigger listeners registered with `watch`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// let mut res = Resources::new();
+    ///
+    /// assert_eq!(*res.get_or_insert(5u32), 5);
+    /// assert_eq!(*res.get_or_insert(7u32), 5);
+    /// ```
+    pub fn get_or_insert<T>(&mut self, value: T) -> Fetch<'_, T>
+    where
+        T: Resource,
+    {
+        if !self.has_value(ResourceId::new::<T>()) {
+            self.add(value);
+        }
+
+        self.fetch(0)
+    }
+
+    /// Registers a listener for the resource with type `T` and id 0.
+    ///
+    /// The listener gets called with the current value every time
+    /// write access to the resource ends, that is whenever a
+    /// `FetchMut` (or `FetchIdMut`) of it gets dropped, whether or not
+    /// the value was changed. It isn't called if the borrow is dropped
+    /// while panicking. Multiple listeners may be registered for the
+    /// same resource; they are called in the order they were registered in.
+    ///
+    /// Changes which don't go through such a borrow (`replace`,
+    /// `get_raw_mut`, `restore`, `merge` and the rollback
+    /// of a `transaction`) don't call listeners.
+    ///
+    /// Please note that listeners are called while the resource
+    /// is still borrowed, so they must not fetch it themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// struct Counter(u32);
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(Counter(0));
+    /// res.watch(|c: &Counter| println!("Counter changed to {}", c.0));
+    ///
+    /// res.fetch_mut::<Counter>(0).0 += 1;
+    /// ```
+    pub fn watch<T, F>(&mut self, f: F)
+    where
+        T: Resource,
+        F: Fn(&T) + Send + Sync + 'static,
+    {
+        let listener = move |r: &dyn Resource| if let Some(r) = r.downcast_ref::<T>() {
+            f(r);
+        };
+
+        self.listeners
+            .entry(ResourceId::new::<T>())
+            .or_default()
+            .push(Box::new(listener));
     }
 
     /// Fetches the resource with the specified type `T`.
@@ -305,12 +699,14 @@ impl Resources {
     /// # Panics
     ///
     /// Panics if the resource is being accessed mutably.
-    /// Also panics if there is no such resource.
+    /// Also panics if there is no such resource; the panic message
+    /// contains the type name and the id of the missing resource.
     pub fn fetch<T>(&self, id: usize) -> Fetch<T>
     where
         T: Resource,
     {
-        self.try_fetch(id).expect(RESOURCE_NOT_FOUND)
+        self.try_fetch(id)
+            .unwrap_or_else(|| resource_not_found::<T>(id))
     }
 
     /// Like `fetch`, but returns an `Option` instead of panicking in the case of the resource
@@ -319,6 +715,9 @@ impl Resources {
     where
         T: Resource,
     {
+        #[cfg(feature = "log")]
+        trace!("Resources: fetch {:?} (read)", ::std::any::type_name::<T>());
+
         self.try_fetch_internal(TypeId::of::<T>(), id).map(|r| {
             Fetch {
                 inner: r.borrow(),
@@ -334,7 +733,8 @@ impl Resources {
     where
         T: Resource,
     {
-        self.try_fetch_mut(id).expect(RESOURCE_NOT_FOUND)
+        self.try_fetch_mut(id)
+            .unwrap_or_else(|| resource_not_found::<T>(id))
     }
 
     /// Like `fetch_mut`, but returns an `Option` instead of panicking in the case of the resource
@@ -343,14 +743,99 @@ impl Resources {
     where
         T: Resource,
     {
+        #[cfg(feature = "log")]
+        trace!("Resources: fetch {:?} (write)", ::std::any::type_name::<T>());
+
         self.try_fetch_internal(TypeId::of::<T>(), id).map(|r| {
             FetchMut {
-                inner: r.borrow_mut(),
+                inner: WriteBorrow::new(r.borrow_mut(), self.listeners_of(TypeId::of::<T>(), id)),
                 phantom: PhantomData,
             }
         })
     }
 
+    /// Calls `f` with the resource with type `T` and id 0 and returns
+    /// its result, or `None` if there is no such resource.
+    ///
+    /// The resource is only borrowed while `f` runs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is being accessed mutably.
+    pub fn map_resource<T, R, F>(&self, f: F) -> Option<R>
+    where
+        T: Resource,
+        F: FnOnce(&T) -> R,
+    {
+        self.try_fetch::<T>(0).map(|fetched| f(&fetched))
+    }
+
+    /// Returns the number of elements of the collection resource with
+    /// type `T` and id 0, or `None` if there is no such resource.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(vec![1u32, 2, 3]);
+    ///
+    /// assert_eq!(res.len_of::<Vec<u32>>(), Some(3));
+    /// assert_eq!(res.len_of::<Vec<u8>>(), None);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is being accessed mutably.
+    pub fn len_of<T>(&self) -> Option<usize>
+    where
+        T: Resource,
+        for<'c> &'c T: IntoIterator,
+        for<'c> <&'c T as IntoIterator>::IntoIter: ExactSizeIterator,
+    {
+        self.map_resource(|collection: &T| collection.into_iter().len())
+    }
+
+    /// Returns an iterator over the ids of all resources together
+    /// with the resources themselves, in arbitrary order.
+    ///
+    /// # Panics
+    ///
+    /// The iterator panics if it reaches a resource
+    /// which is being accessed mutably.
+    pub fn iter(&self) -> ResourcesIter<'_> {
+        ResourcesIter(self.resources.iter())
+    }
+
+    /// Calls `f` with a `ScopedResources`, which keeps every resource
+    /// fetched through it borrowed until `f` returns. All of these
+    /// borrows are released together, in reverse order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(2u32);
+    /// res.add(0u64);
+    ///
+    /// res.scope(|s| {
+    ///     let factor = s.fetch::<u32>(0);
+    ///     let product = s.fetch_mut::<u64>(0);
+    ///     *product = 21 * *factor as u64;
+    /// });
+    ///
+    /// assert_eq!(*res.fetch::<u64>(0), 42);
+    /// ```
+    pub fn scope<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&ScopedResources) -> R,
+    {
+        f(&create_scope(self))
+    }
+
     /// Fetches the resource with the specified type id.
     ///
     /// Please see `fetch` for details.
@@ -384,16 +869,246 @@ impl Resources {
     pub fn try_fetch_id_mut(&self, id: TypeId, comp_id: usize) -> Option<FetchIdMut> {
         self.try_fetch_internal(id, comp_id).map(|r| {
             FetchIdMut {
-                inner: r.borrow_mut(),
+                inner: WriteBorrow::new(r.borrow_mut(), self.listeners_of(id, comp_id)),
             }
         })
     }
 
+    /// Mutates the resources of type `T1` and `T2` (both with id 0)
+    /// in parallel, by running `f1` and `f2` with rayon's `join`.
+    ///
+    /// Only available on platforms with
+    /// multithreading support (so not on emscripten).
+    ///
+    /// # Panics
+    ///
+    /// * if `T1` and `T2` are the same type
+    /// * if one of the resources doesn't exist or is already borrowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::Resources;
+    ///
+    /// struct Positions(Vec<f32>);
+    /// struct Names(Vec<String>);
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(Positions(vec![1.0, 2.0]));
+    /// res.add(Names(vec![]));
+    ///
+    /// res.par_apply(
+    ///     |p: &mut Positions| p.0.iter_mut().for_each(|x| *x *= 2.0),
+    ///     |n: &mut Names| n.0.push("Bob".to_owned()),
+    /// );
+    ///
+    /// assert_eq!(res.fetch::<Positions>(0).0, vec![2.0, 4.0]);
+    /// ```
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn par_apply<T1, T2, F1, F2>(&self, f1: F1, f2: F2)
+    where
+        T1: Resource,
+        T2: Resource,
+        F1: FnOnce(&mut T1) + Send,
+        F2: FnOnce(&mut T2) + Send,
+    {
+        assert_ne!(
+            ResourceId::new::<T1>(),
+            ResourceId::new::<T2>(),
+            "`par_apply` needs two different resources"
+        );
+
+        let mut r1 = self.fetch_mut::<T1>(0);
+        let mut r2 = self.fetch_mut::<T2>(0);
+        let (r1, r2) = (&mut *r1, &mut *r2);
+
+        ::rayon::join(move || f1(r1), move || f2(r2));
+    }
+
+    fn listeners_of(&self, id: TypeId, cid: usize) -> &[Listener] {
+        self.listeners
+            .get(&ResourceId(id, cid))
+            .map(|l| &l[..])
+            .unwrap_or(&[])
+    }
+
+    /// Returns the resource with the given id as a type-erased
+    /// `Resource`, which can be downcasted with `downcast_ref`.
+    ///
+    /// This is meant for scripting and reflection, where the type
+    /// of a resource isn't known at compile time.
+    ///
+    /// # Safety
+    ///
+    /// This bypasses the borrow checking `fetch` does:
+    /// the resource must not be fetched mutably (e.g. by a system being
+    /// dispatched) while the returned reference is alive.
+    /// Prefer `fetch_id` or `get_raw_mut` if possible.
+    pub unsafe fn get_raw(&self, id: ResourceId) -> Option<&dyn Resource> {
+        self.resources.get(&id).map(|r| r.get_unchecked().as_ref())
+    }
+
+    /// Returns the resource with the given id as a type-erased
+    /// `Resource`, which can be downcasted with `downcast_mut`.
+    ///
+    /// Since this requires `&mut self`, no other borrow can be alive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shred::{ResourceId, Resources};
+    ///
+    /// let mut res = Resources::new();
+    /// res.add(5u32);
+    ///
+    /// let value = res.get_raw_mut(ResourceId::new::<u32>()).unwrap();
+    /// *value.downcast_mut::<u32>().unwrap() += 1;
+    ///
+    /// assert_eq!(*res.fetch::<u32>(0), 6);
+    /// ```
+    pub fn get_raw_mut(&mut self, id: ResourceId) -> Option<&mut dyn Resource> {
+        self.resources.get_mut(&id).map(|r| r.get_mut().as_mut())
+    }
+
+    fn record_type_name<R: Resource>(&mut self) {
+        self.type_names
+            .insert(TypeId::of::<R>(), ::std::any::type_name::<R>());
+    }
+
     fn try_fetch_internal(&self, id: TypeId, cid: usize) -> Option<&TrustCell<Box<Resource>>> {
         self.resources.get(&ResourceId(id, cid))
     }
 }
 
+/// A copy of the clonable resources of a `Resources`
+/// container, created by [`Resources::snapshot`].
+///
+/// [`Resources::snapshot`]: struct.Resources.html#method.snapshot
+#[derive(Debug)]
+pub struct Snapshot(Resources);
+
+/// The state a failed `Resources::transaction` is rolled back to.
+struct Checkpoint {
+    ids: FnvHashSet<ResourceId>,
+    cloners: FnvHashMap<TypeId, Cloner>,
+    listeners: FnvHashMap<ResourceId, usize>,
+    snapshot: Snapshot,
+}
+
+/// An iterator over the resources of a `Resources` container,
+/// created by [`Resources::iter`].
+///
+/// [`Resources::iter`]: struct.Resources.html#method.iter
+pub struct ResourcesIter<'a>(
+    ::std::collections::hash_map::Iter<'a, ResourceId, TrustCell<Box<dyn Resource>>>,
+);
+
+impl<'a> Iterator for ResourcesIter<'a> {
+    type Item = (ResourceId, FetchId<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|(&id, cell)| (id, FetchId { inner: cell.borrow() }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> IntoIterator for &'a Resources {
+    type Item = (ResourceId, FetchId<'a>);
+    type IntoIter = ResourcesIter<'a>;
+
+    fn into_iter(self) -> ResourcesIter<'a> {
+        self.iter()
+    }
+}
+
+/// An iterator moving the resources out of a `Resources` container,
+/// created by its `IntoIterator` implementation.
+pub struct ResourcesIntoIter(
+    ::std::collections::hash_map::IntoIter<ResourceId, TrustCell<Box<dyn Resource>>>,
+);
+
+impl Iterator for ResourcesIntoIter {
+    type Item = (ResourceId, Box<dyn Resource>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(id, cell)| (id, cell.into_inner()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl IntoIterator for Resources {
+    type Item = (ResourceId, Box<dyn Resource>);
+    type IntoIter = ResourcesIntoIter;
+
+    fn into_iter(self) -> ResourcesIntoIter {
+        ResourcesIntoIter(self.resources.into_iter())
+    }
+}
+
+fn clone_resource<R>(r: &dyn Resource) -> Box<dyn Resource>
+where
+    R: Resource + Clone,
+{
+    let r = r.downcast_ref::<R>()
+        .expect("Clone function registered for the wrong type");
+
+    Box::new(r.clone())
+}
+
+pub fn resource_not_found<T>(id: usize) -> ! {
+    use std::any::type_name;
+
+    panic!("{} (`{}` with id {})", RESOURCE_NOT_FOUND, type_name::<T>(), id)
+}
+
+/// Lists the type names and ids of all resources together with
+/// the way they are currently borrowed, e.g.
+/// `Resources {u32(0): Read x2, alloc::string::String(1): free}`.
+///
+/// Resources whose type name isn't known
+/// are listed by their `ResourceId` instead.
+impl fmt::Debug for Resources {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut resources: Vec<_> = self.resources.iter().collect();
+        resources.sort_by_key(|&(id, _)| *id);
+
+        let entries = resources.into_iter().map(|(id, cell)| {
+            let name = DebugId {
+                id,
+                type_name: self.type_names.get(&id.0).cloned(),
+            };
+
+            (name, cell.borrow_state())
+        });
+
+        f.write_str("Resources ")?;
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+/// Prints a `ResourceId` as `{type name}({id})` if the type name is known.
+struct DebugId<'a> {
+    id: &'a ResourceId,
+    type_name: Option<&'static str>,
+}
+
+impl<'a> fmt::Debug for DebugId<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.type_name {
+            Some(name) => write!(f, "{}({})", name, self.id.1),
+            None => write!(f, "{:?}", self.id),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -409,6 +1124,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn res_id_as_key() {
+        use std::collections::{BTreeSet, HashSet};
+
+        struct Other;
+
+        let ids = [
+            ResourceId::new::<Res>(),
+            ResourceId::new_with_id::<Res>(1),
+            ResourceId::new::<Other>(),
+        ];
+
+        let hash_set: HashSet<_> = ids.iter().cloned().collect();
+        let btree_set: BTreeSet<_> = ids.iter().cloned().collect();
+
+        for id in &ids {
+            assert!(hash_set.contains(id));
+            assert!(btree_set.contains(id));
+        }
+
+        assert!(!hash_set.contains(&ResourceId::new_with_id::<Other>(1)));
+        assert!(!btree_set.contains(&ResourceId::new_with_id::<Other>(1)));
+    }
+
     #[test]
     fn fetch_aspects() {
         assert_eq!(
@@ -467,6 +1206,325 @@ mod tests {
         let read = res.fetch::<Res>(0);
     }
 
+    #[test]
+    fn watch() {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut res = Resources::new();
+        res.add(5i32);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        {
+            let calls = calls.clone();
+            res.watch(move |x: &i32| {
+                assert_eq!(*x, 6);
+                calls.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        {
+            let calls = calls.clone();
+            res.watch(move |x: &i32| {
+                assert_eq!(*x, 6);
+                calls.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        res.fetch::<i32>(0);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        *res.fetch_mut::<i32>(0) += 1;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            let _value = res.fetch_mut::<i32>(0);
+            panic!("Panic while borrowed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn debug() {
+        let mut res = Resources::new();
+        res.add_with_id(Res, 0);
+        res.add_with_id(Res, 1);
+        res.add_with_id(Res, 2);
+
+        let _read = res.fetch::<Res>(0);
+        let _write = res.fetch_mut::<Res>(1);
+
+        let output = format!("{:?}", res);
+        let expected = "Resources {shred::res::tests::Res(0): Read x1, \
+                        shred::res::tests::Res(1): Write, \
+                        shred::res::tests::Res(2): free}";
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn get_raw() {
+        let mut res = Resources::new();
+        res.add(5u32);
+
+        {
+            let value = unsafe { res.get_raw(ResourceId::new::<u32>()) }.unwrap();
+            assert_eq!(value.downcast_ref::<u32>(), Some(&5));
+            assert!(value.downcast_ref::<i32>().is_none());
+        }
+
+        *res.get_raw_mut(ResourceId::new::<u32>())
+            .unwrap()
+            .downcast_mut::<u32>()
+            .unwrap() = 7;
+        assert_eq!(*res.fetch::<u32>(0), 7);
+
+        assert!(unsafe { res.get_raw(ResourceId::new::<Res>()) }.is_none());
+        assert!(res.get_raw_mut(ResourceId::new::<Res>()).is_none());
+    }
+
+    #[cfg(not(target_os = "emscripten"))]
+    #[test]
+    fn par_apply() {
+        let mut res = Resources::new();
+        res.add(1u32);
+        res.add(String::from("a"));
+
+        res.par_apply(|n: &mut u32| *n += 1, |s: &mut String| s.push('b'));
+
+        assert_eq!(*res.fetch::<u32>(0), 2);
+        assert_eq!(*res.fetch::<String>(0), "ab");
+    }
+
+    #[cfg(not(target_os = "emscripten"))]
+    #[test]
+    #[should_panic(expected = "`par_apply` needs two different resources")]
+    fn par_apply_same_resource() {
+        let mut res = Resources::new();
+        res.add(1u32);
+
+        res.par_apply(|_: &mut u32| {}, |_: &mut u32| {});
+    }
+
+    #[test]
+    fn transaction() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let mut res = Resources::new();
+        res.add_clone(1u32);
+
+        let r: Result<_, ()> = res.transaction(|res| {
+            *res.fetch_mut::<u32>(0) += 1;
+
+            Ok(5)
+        });
+        assert_eq!(r, Ok(5));
+        assert_eq!(*res.fetch::<u32>(0), 2);
+
+        let r: Result<(), _> = res.transaction(|res| {
+            *res.fetch_mut::<u32>(0) += 1;
+            res.add(Res);
+
+            Err("failed")
+        });
+        assert_eq!(r, Err("failed"));
+        assert_eq!(*res.fetch::<u32>(0), 2);
+        assert!(!res.has_value(ResourceId::new::<Res>()));
+
+        res.add(String::from("kept"));
+        let r: Result<(), _> = res.transaction(|res| {
+            res.fetch_mut::<String>(0).push_str(" changed");
+            res.register_clone_factory::<String>();
+            res.watch(|_: &u32| panic!("Listener wasn't removed"));
+
+            Err("failed")
+        });
+        assert_eq!(r, Err("failed"));
+        assert_eq!(*res.fetch::<String>(0), "kept changed");
+        assert_eq!(res.try_clone().1, vec![ResourceId::new::<String>()]);
+        // Panics if the listener is still registered.
+        *res.fetch_mut::<u32>(0) = 2;
+
+        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _: Result<(), ()> = res.transaction(|res| {
+                let mut n = res.fetch_mut::<u32>(0);
+                *n += 1;
+
+                panic!("Transaction panicked");
+            });
+        }));
+        assert!(r.is_err());
+        assert_eq!(*res.fetch::<u32>(0), 2);
+        assert!(res.try_fetch_mut::<u32>(0).is_some());
+    }
+
+    #[test]
+    fn get_or_insert() {
+        let mut res = Resources::new();
+
+        assert_eq!(*res.get_or_insert(String::from("first")), "first");
+        assert_eq!(*res.get_or_insert(String::from("second")), "first");
+        assert_eq!(*res.fetch::<String>(0), "first");
+    }
+
+    #[test]
+    fn map_resource() {
+        let mut res = Resources::new();
+        res.add(vec![1u32, 2, 3]);
+
+        assert_eq!(res.map_resource(|v: &Vec<u32>| v[1]), Some(2));
+        assert_eq!(res.map_resource(|v: &Vec<u8>| v[1]), None);
+
+        assert_eq!(res.len_of::<Vec<u32>>(), Some(3));
+        res.fetch_mut::<Vec<u32>>(0).push(4);
+        assert_eq!(res.len_of::<Vec<u32>>(), Some(4));
+    }
+
+    #[test]
+    fn iter() {
+        use std::collections::HashSet;
+
+        let mut res = Resources::new();
+        res.add(5u32);
+        res.add_with_id(6u32, 1);
+        res.add(Res);
+
+        let expected: HashSet<_> = vec![
+            ResourceId::new::<u32>(),
+            ResourceId::new_with_id::<u32>(1),
+            ResourceId::new::<Res>(),
+        ].into_iter()
+            .collect();
+
+        let ids: HashSet<_> = res.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, expected);
+
+        let sum: u32 = (&res)
+            .into_iter()
+            .filter_map(|(_, r)| r.downcast_ref::<u32>().cloned())
+            .sum();
+        assert_eq!(sum, 11);
+
+        let owned: Vec<_> = res.into_iter().collect();
+        assert_eq!(owned.len(), 3);
+        let ids: HashSet<_> = owned.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, expected);
+        assert!(owned.iter().any(|(_, r)| r.downcast_ref::<u32>() == Some(&6)));
+    }
+
+    #[test]
+    fn replace() {
+        let mut res = Resources::new();
+        res.add(vec![1u32, 2]);
+        res.add_with_id(vec![3u32], 1);
+
+        assert_eq!(res.replace(vec![4u32]), Some(vec![1, 2]));
+        assert_eq!(*res.fetch::<Vec<u32>>(0), vec![4]);
+        assert_eq!(*res.fetch::<Vec<u32>>(1), vec![3]);
+
+        assert_eq!(res.replace(5u8), None);
+        assert_eq!(*res.fetch::<u8>(0), 5);
+    }
+
+    #[test]
+    fn merge() {
+        let mut base = Resources::new();
+        base.add(1u32);
+        base.add(Res);
+        base.register_clone_factory::<u32>();
+
+        let mut level = Resources::new();
+        level.add(2u32);
+        level.add(String::from("level"));
+
+        base.merge(level);
+        assert_eq!(base.iter().count(), 3);
+        assert_eq!(*base.fetch::<u32>(0), 2);
+        assert_eq!(*base.fetch::<String>(0), "level");
+        assert!(base.has_value(ResourceId::new::<Res>()));
+        assert_eq!(base.try_clone().1.len(), 2);
+    }
+
+    #[test]
+    fn scope() {
+        let mut res = Resources::new();
+        res.add(1u32);
+        res.add(String::from("a"));
+
+        let len = res.scope(|s| {
+            *s.fetch_mut::<u32>(0) += 1;
+            let text = s.fetch_mut::<String>(0);
+            text.push('b');
+
+            text.len()
+        });
+
+        assert_eq!(len, 2);
+        assert_eq!(*res.fetch::<u32>(0), 2);
+        assert_eq!(*res.fetch::<String>(0), "ab");
+    }
+
+    #[test]
+    #[should_panic]
+    fn scope_fetch_twice_mut() {
+        let mut res = Resources::new();
+        res.add(1u32);
+
+        res.scope(|s| {
+            s.fetch::<u32>(0);
+            s.fetch_mut::<u32>(0);
+        });
+    }
+
+    #[test]
+    fn snapshot() {
+        #[derive(Clone)]
+        struct Name(&'static str);
+
+        let mut res = Resources::new();
+        res.add(Name("a"));
+        res.add_with_id(3u32, 2);
+        res.add(Res);
+        res.register_clone_factory::<Name>();
+        res.register_clone_factory::<u32>();
+
+        let snapshot = res.snapshot();
+        res.fetch_mut::<Name>(0).0 = "b";
+        *res.fetch_mut::<u32>(2) = 4;
+
+        res.restore(snapshot);
+        assert_eq!(res.fetch::<Name>(0).0, "a");
+        assert_eq!(*res.fetch::<u32>(2), 3);
+        assert!(res.has_value(ResourceId::new::<Res>()));
+    }
+
+    #[test]
+    fn try_clone() {
+        #[derive(Clone)]
+        struct Name(&'static str);
+
+        let mut res = Resources::new();
+        res.add_clone(Name("a"));
+        res.add_clone_with_id(3u32, 1);
+        res.add(Res);
+
+        let (mut cloned, excluded) = res.try_clone();
+        assert_eq!(excluded, vec![ResourceId::new::<Res>()]);
+
+        cloned.fetch_mut::<Name>(0).0 = "b";
+        *cloned.fetch_mut::<u32>(1) += 1;
+        assert_eq!(res.fetch::<Name>(0).0, "a");
+        assert_eq!(*res.fetch::<u32>(1), 3);
+        assert_eq!(*cloned.fetch::<u32>(1), 4);
+        assert!(!cloned.has_value(ResourceId::new::<Res>()));
+
+        cloned.add(Res);
+        let (_, excluded) = cloned.try_clone();
+        assert_eq!(excluded, vec![ResourceId::new::<Res>()]);
+    }
+
     #[test]
     fn fetch_uses_id() {
         let mut res = Resources::new();
@@ -0,0 +1,94 @@
+use std::any::TypeId;
+use std::cell::RefCell;
+
+use res::{resource_not_found, FetchId, FetchIdMut, Resource, Resources};
+
+/// A set of resource borrows which are released together,
+/// created by [`Resources::scope`].
+///
+/// Every `fetch` / `fetch_mut` keeps the resource borrowed
+/// until the scope ends; the borrows are then released in the
+/// reverse order they were acquired in. Fetching a resource which is
+/// already borrowed mutably in the same scope panics.
+///
+/// [`Resources::scope`]: struct.Resources.html#method.scope
+pub struct ScopedResources<'a> {
+    res: &'a Resources,
+    guards: RefCell<Vec<Guard<'a>>>,
+}
+
+enum Guard<'a> {
+    Read(FetchId<'a>),
+    Write(FetchIdMut<'a>),
+}
+
+impl<'a> ScopedResources<'a> {
+    /// Borrows the resource with type `T` and id `id`
+    /// immutably until the end of the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is borrowed mutably or doesn't exist.
+    pub fn fetch<T>(&self, id: usize) -> &T
+    where
+        T: Resource,
+    {
+        let fetched = self.res
+            .try_fetch_id(TypeId::of::<T>(), id)
+            .unwrap_or_else(|| resource_not_found::<T>(id));
+        let ptr = unsafe { fetched.downcast_ref_unchecked::<T>() } as *const T;
+
+        self.guards.borrow_mut().push(Guard::Read(fetched));
+
+        // The guard keeps the resource borrowed as long as `self` lives,
+        // and moving the guard doesn't move the (boxed) resource.
+        unsafe { &*ptr }
+    }
+
+    /// Borrows the resource with type `T` and id `id`
+    /// mutably until the end of the scope.
+    ///
+    /// Listeners registered with `Resources::watch`
+    /// are notified once the scope ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource is already borrowed or doesn't exist.
+    #[allow(clippy::mut_from_ref)]
+    pub fn fetch_mut<T>(&self, id: usize) -> &mut T
+    where
+        T: Resource,
+    {
+        let mut fetched = self.res
+            .try_fetch_id_mut(TypeId::of::<T>(), id)
+            .unwrap_or_else(|| resource_not_found::<T>(id));
+        let ptr = unsafe { fetched.downcast_mut_unchecked::<T>() } as *mut T;
+
+        self.guards.borrow_mut().push(Guard::Write(fetched));
+
+        // See `fetch`; the borrow is exclusive, so there is
+        // no other reference to the resource.
+        unsafe { &mut *ptr }
+    }
+}
+
+impl<'a> Drop for ScopedResources<'a> {
+    fn drop(&mut self) {
+        let guards = self.guards.get_mut();
+
+        while let Some(guard) = guards.pop() {
+            match guard {
+                Guard::Read(fetched) => drop(fetched),
+                Guard::Write(fetched) => drop(fetched),
+            }
+        }
+    }
+}
+
+/// Creates the scope used by `Resources::scope`.
+pub fn create_scope(res: &Resources) -> ScopedResources<'_> {
+    ScopedResources {
+        res,
+        guards: RefCell::new(Vec::new()),
+    }
+}
@@ -13,6 +13,12 @@ pub trait RunNow<'a> {
     /// (tries to read from a resource which is already written to or
     /// tries to write to a resource which is read from).
     fn run_now(&mut self, res: &'a Resources);
+
+    /// Resets the state the system keeps between runs.
+    ///
+    /// Named differently from `System::reset`, so both traits can be
+    /// imported at the same time. Defaults to doing nothing.
+    fn reset_now(&mut self) {}
 }
 
 impl<'a, T> RunNow<'a> for T
@@ -23,6 +29,10 @@ where
         let data = T::SystemData::fetch(res, 0);
         self.run(data);
     }
+
+    fn reset_now(&mut self) {
+        self.reset();
+    }
 }
 
 #[repr(u8)]
@@ -60,6 +70,14 @@ pub trait System<'a> {
     fn running_time(&self) -> RunningTime {
         RunningTime::Average
     }
+
+    /// Clears any state (like caches) the system keeps between runs.
+    /// This is called by [`Dispatcher::reset`].
+    ///
+    /// Defaults to doing nothing.
+    ///
+    /// [`Dispatcher::reset`]: struct.Dispatcher.html#method.reset
+    fn reset(&mut self) {}
 }
 
 /// A struct implementing
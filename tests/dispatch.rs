@@ -63,6 +63,34 @@ fn dispatch_builder() {
         .build();
 }
 
+#[test]
+fn dispatch_builder_debug() {
+    use shred::ResourceId;
+
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySysMut, "b", &["a"])
+        .with(DummySys, "c", &["a", "b"]);
+
+    let output = format!("{:?}", builder);
+    let res = format!("{:?}", ResourceId::new::<Res>());
+
+    assert!(output.starts_with("DispatcherBuilder { systems: ["));
+    assert!(output.contains(&format!(
+        "System {{ name: \"a\", dependencies: [], reads: [{}], writes: [] }}",
+        res
+    )));
+    assert!(output.contains(&format!(
+        "System {{ name: \"b\", dependencies: [\"a\"], reads: [], writes: [{}] }}",
+        res
+    )));
+    assert!(output.contains("name: \"c\", dependencies: [\"a\", \"b\"]"));
+    assert!(output.find("\"a\"").unwrap() < output.find("\"b\"").unwrap());
+
+    let pretty = format!("{:#?}", builder);
+    assert!(pretty.contains("name: \"c\","));
+}
+
 #[test]
 #[should_panic(expected = "No such system registered")]
 fn dispatch_builder_invalid() {
@@ -72,6 +100,110 @@ fn dispatch_builder_invalid() {
         .build();
 }
 
+#[test]
+fn dispatch_panic_location() {
+    use shred::DispatchError;
+
+    struct Panic;
+
+    impl<'a> System<'a> for Panic {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            panic!("Propagated panic");
+        }
+    }
+
+    let mut builder = DispatcherBuilder::new();
+    let line = line!() + 1;
+    builder.add(Panic, "p", &[]);
+    let mut d: Dispatcher = builder.build();
+
+    match d.try_dispatch(&Resources::new()) {
+        Err(DispatchError::SystemPanic { location, .. }) => {
+            assert_eq!((location.file(), location.line()), (file!(), line));
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn dispatch_missing_resource() {
+    use shred::DispatchError;
+
+    let mut res = Resources::new();
+    res.add(ResB);
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .build();
+
+    match d.try_dispatch(&res) {
+        Err(DispatchError::SystemPanic { name, message, .. }) => {
+            assert_eq!(name, "a");
+            assert_eq!(
+                message.0,
+                "No resource with the given id (`dispatch::Res` with id 0)"
+            );
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn dispatch_added_dependency() {
+    struct Value(u32);
+
+    struct Check;
+
+    impl<'a> System<'a> for Check {
+        type SystemData = Fetch<'a, Value>;
+
+        fn run(&mut self, value: Self::SystemData) {
+            assert_eq!(value.0, 1);
+        }
+    }
+
+    struct Set;
+
+    impl<'a> System<'a> for Set {
+        type SystemData = FetchMut<'a, Value>;
+
+        fn run(&mut self, mut value: Self::SystemData) {
+            value.0 = 1;
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Value(0));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Check, "check", &[])
+        .with(Set, "set", &[])
+        .with_dependency("set", "check")
+        .build();
+
+    d.dispatch(&res);
+}
+
+#[test]
+#[should_panic(expected = "Cyclic dependency between the systems")]
+fn dispatch_added_dependency_cycle() {
+    DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "b", &["a"])
+        .with_dependency("b", "a")
+        .build();
+}
+
+#[test]
+#[should_panic(expected = "No such system registered (\"z\")")]
+fn dispatch_added_dependency_invalid() {
+    DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with_dependency("z", "a");
+}
+
 #[test]
 fn dispatch_basic() {
     let mut res = Resources::new();
@@ -173,6 +305,989 @@ fn dispatch_async_res() {
     res.add_with_id(Res, 2);
 }
 
+#[test]
+fn dispatch_watch() {
+    use std::sync::{Arc, Mutex};
+
+    struct Counter(u32);
+
+    struct Increment;
+
+    impl<'a> System<'a> for Increment {
+        type SystemData = FetchMut<'a, Counter>;
+
+        fn run(&mut self, mut counter: Self::SystemData) {
+            counter.0 += 1;
+        }
+    }
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+
+    let mut res = Resources::new();
+    res.add(Counter(0));
+
+    for listener in 0..2 {
+        let seen = seen.clone();
+        res.watch(move |c: &Counter| seen.lock().unwrap().push((listener, c.0)));
+    }
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Increment, "increment", &[])
+        .build();
+
+    d.dispatch(&res);
+
+    assert_eq!(*seen.lock().unwrap(), vec![(0, 1), (1, 1)]);
+}
+
+#[test]
+fn dispatch_closure() {
+    use shred::ResourceId;
+
+    let mut res = Resources::new();
+    res.add(5u32);
+    res.add(String::new());
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with_from_closure(
+            vec![ResourceId::new::<u32>()],
+            vec![ResourceId::new::<String>()],
+            |res: &Resources| {
+                let number = res.fetch::<u32>(0);
+                *res.fetch_mut::<String>(0) = format!("number: {}", *number);
+            },
+            "closure",
+            &[],
+        )
+        .build();
+
+    d.dispatch(&res);
+
+    assert_eq!(*res.fetch::<String>(0), "number: 5");
+}
+
+#[test]
+fn dispatch_cancellable() {
+    use shred::{CancellationToken, Cancelled};
+
+    struct Count(u32);
+
+    struct Cancel(CancellationToken);
+
+    impl<'a> System<'a> for Cancel {
+        type SystemData = FetchMut<'a, Count>;
+
+        fn run(&mut self, mut count: Self::SystemData) {
+            self.0.cancel();
+            sleep_short();
+            count.0 += 1;
+        }
+    }
+
+    struct Increment;
+
+    impl<'a> System<'a> for Increment {
+        type SystemData = FetchMut<'a, Count>;
+
+        fn run(&mut self, mut count: Self::SystemData) {
+            count.0 += 10;
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Count(0));
+
+    let token = CancellationToken::new();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Increment, "first", &[])
+        .with(Cancel(token.clone()), "cancel", &["first"])
+        .with(Increment, "skipped", &["cancel"])
+        .build();
+
+    assert_eq!(d.dispatch_cancellable(&res, &token), Err(Cancelled { ran: 2 }));
+    assert_eq!(res.fetch::<Count>(0).0, 11);
+
+    let token = CancellationToken::new();
+    token.cancel();
+    assert_eq!(d.dispatch_cancellable(&res, &token), Err(Cancelled { ran: 0 }));
+    assert_eq!(res.fetch::<Count>(0).0, 11);
+
+    let token = CancellationToken::new();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Increment, "a", &[])
+        .with(Increment, "b", &["a"])
+        .with_thread_local(Cancel(token.clone()))
+        .with_thread_local(Increment)
+        .build();
+    assert_eq!(d.dispatch_cancellable(&res, &token), Err(Cancelled { ran: 3 }));
+    assert_eq!(res.fetch::<Count>(0).0, 32);
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Increment, "a", &[])
+        .with(Increment, "b", &["a"])
+        .build();
+
+    assert_eq!(d.dispatch_cancellable(&res, &CancellationToken::new()), Ok(2));
+    assert_eq!(res.fetch::<Count>(0).0, 52);
+}
+
+#[test]
+fn dispatch_reset() {
+    struct Count(u32);
+
+    struct Cached(Option<u32>);
+
+    impl<'a> System<'a> for Cached {
+        type SystemData = FetchMut<'a, Count>;
+
+        fn run(&mut self, mut count: Self::SystemData) {
+            let cached = *self.0.get_or_insert(count.0 + 1);
+            count.0 = cached;
+        }
+
+        fn reset(&mut self) {
+            self.0 = None;
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Count(0));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Cached(None), "cached", &[])
+        .build();
+
+    d.dispatch(&res);
+    d.dispatch(&res);
+    assert_eq!(res.fetch::<Count>(0).0, 1);
+
+    d.reset();
+    d.dispatch(&res);
+    assert_eq!(res.fetch::<Count>(0).0, 2);
+
+    {
+        // Both traits can be in scope.
+        use shred::RunNow;
+
+        let mut sys = Cached(Some(3));
+        sys.reset();
+        assert_eq!(sys.0, None);
+
+        sys.0 = Some(3);
+        sys.reset_now();
+        assert_eq!(sys.0, None);
+    }
+}
+
+#[test]
+fn dispatch_collect_errors() {
+    use shred::{BuildError, ErrorStrategy};
+
+    let errors = DispatcherBuilder::new()
+        .with_error_strategy(ErrorStrategy::Collect)
+        .with(DummySys, "a", &["missing"])
+        .with(DummySys, "a", &[])
+        .with_barrier()
+        .with(DummySys, "b", &[])
+        .with_dependency("b", "a")
+        .try_build()
+        .err()
+        .expect("Building should fail");
+
+    assert_eq!(
+        errors,
+        vec![
+            BuildError::UnknownSystem("missing".to_owned()),
+            BuildError::DuplicateName("a".to_owned()),
+            BuildError::DependencyAfterBarrier {
+                system: "a".to_owned(),
+                dependency: "b".to_owned(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn dispatch_step() {
+    use std::sync::{Arc, Mutex};
+
+    struct Record(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+    impl<'a> System<'a> for Record {
+        type SystemData = FetchMut<'a, Res>;
+
+        fn run(&mut self, _: Self::SystemData) {
+            self.1.lock().unwrap().push(self.0);
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Res);
+
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Record("c", order.clone()), "c", &[])
+        .with(Record("a", order.clone()), "a", &["c"])
+        .with(Record("b", order.clone()), "b", &["a"])
+        .with_thread_local(Record("local", order.clone()))
+        .build();
+
+    let mut steps = Vec::new();
+    while let Some(step) = d.step(&res) {
+        steps.push((step.name, step.id, step.pending));
+    }
+
+    assert_eq!(
+        steps,
+        vec![
+            ("c".to_owned(), Some(0), true),
+            ("a".to_owned(), Some(1), true),
+            ("b".to_owned(), Some(2), true),
+            ("".to_owned(), None, false),
+        ]
+    );
+    assert_eq!(*order.lock().unwrap(), vec!["c", "a", "b", "local"]);
+
+    assert_eq!(d.step(&res).map(|step| step.name), Some("c".to_owned()));
+}
+
+#[test]
+fn dispatch_pool_name() {
+    struct ThreadName;
+
+    impl<'a> System<'a> for ThreadName {
+        type SystemData = FetchMut<'a, String>;
+
+        fn run(&mut self, mut name: Self::SystemData) {
+            *name = std::thread::current().name().unwrap_or("").to_owned();
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(String::new());
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(ThreadName, "thread_name", &[])
+        .with_pool_name("physics")
+        .build();
+    d.dispatch(&res);
+    assert!(res.fetch::<String>(0).starts_with("physics-worker-"));
+
+    *res.fetch_mut::<String>(0) = String::new();
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(ThreadName, "thread_name", &[])
+        .with_pool_name("render")
+        .no_default_pool()
+        .build();
+    d.dispatch(&res);
+    assert!(res.fetch::<String>(0).starts_with("render-worker-"));
+}
+
+#[test]
+fn dispatch_try() {
+    use std::error::Error;
+
+    use shred::{DispatchError, PanicMessage};
+
+    struct Panic;
+
+    impl<'a> System<'a> for Panic {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            panic!("PhysicsUpdate failed");
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Res);
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "dummy", &[])
+        .with(Panic, "physics", &["dummy"])
+        .build();
+
+    let error = d.try_dispatch(&res).unwrap_err();
+    let location = match error {
+        DispatchError::SystemPanic { location, .. } => location,
+        _ => panic!("Unexpected error: {:?}", error),
+    };
+    assert_eq!(
+        error,
+        DispatchError::SystemPanic {
+            id: 1,
+            name: "physics".to_owned(),
+            location,
+            message: PanicMessage("PhysicsUpdate failed".to_owned()),
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "System \"physics\" (id=1, registered at {}) panicked: PhysicsUpdate failed",
+            location
+        )
+    );
+    assert_eq!(
+        error.source().map(|e| e.to_string()),
+        Some("PhysicsUpdate failed".to_owned())
+    );
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "dummy", &[])
+        .with_thread_local(DummySys)
+        .with_thread_local(Panic)
+        .build();
+
+    let error = d.try_dispatch(&res).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Thread local system (index=1) panicked: PhysicsUpdate failed"
+    );
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "dummy", &[])
+        .build();
+    assert_eq!(d.try_dispatch(&res), Ok(()));
+}
+
+#[test]
+fn dispatch_budgeted() {
+    use std::time::Duration;
+
+    use shred::{DispatchOutcome, ExecutionBudget};
+
+    struct Runs(Vec<usize>);
+
+    struct Slow(usize);
+
+    impl<'a> System<'a> for Slow {
+        type SystemData = FetchMut<'a, Runs>;
+
+        fn run(&mut self, mut runs: Self::SystemData) {
+            std::thread::sleep(Duration::new(0, 500_000));
+            runs.0.push(self.0);
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Runs(Vec::new()));
+
+    let mut builder = DispatcherBuilder::new()
+        .with_budget(ExecutionBudget::TimeLimit(Duration::new(0, 1_000_000)));
+    for i in 0..10 {
+        builder.add(Slow(i), "", &[]);
+    }
+    let mut d: Dispatcher = builder.build();
+
+    let mut calls = 0;
+    let mut deferred = false;
+    loop {
+        calls += 1;
+        let before = res.fetch::<Runs>(0).0.len();
+        match d.dispatch_budgeted(&res) {
+            DispatchOutcome::Completed { ran } => {
+                assert_eq!(before + ran, 10);
+                break;
+            }
+            DispatchOutcome::BudgetExceeded { completed, pending } => {
+                assert!(!completed.is_empty());
+                assert!(!pending.is_empty());
+                assert_eq!(before + completed.len(), res.fetch::<Runs>(0).0.len());
+                assert_eq!(res.fetch::<Runs>(0).0.len() + pending.len(), 10);
+                deferred = true;
+            }
+        }
+        assert!(calls <= 10);
+    }
+
+    assert!(deferred);
+    let mut runs = res.fetch::<Runs>(0).0.clone();
+    runs.sort();
+    assert_eq!(runs, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn dispatch_budgeted_interrupted() {
+    use std::time::Duration;
+
+    use shred::{DispatchOutcome, ExecutionBudget};
+
+    struct Slow;
+
+    impl<'a> System<'a> for Slow {
+        type SystemData = FetchMut<'a, Res>;
+
+        fn run(&mut self, _: Self::SystemData) {
+            std::thread::sleep(Duration::new(0, 500_000));
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Res);
+
+    let mut builder = DispatcherBuilder::new()
+        .with_budget(ExecutionBudget::TimeLimit(Duration::new(0, 1_000_000)));
+    for _ in 0..10 {
+        builder.add(Slow, "", &[]);
+    }
+    let mut d: Dispatcher = builder.build();
+
+    let first_run = |d: &mut Dispatcher| match d.dispatch_budgeted(&res) {
+        DispatchOutcome::BudgetExceeded { completed, .. } => completed[0],
+        DispatchOutcome::Completed { .. } => panic!("Budget wasn't exceeded"),
+    };
+
+    assert_eq!(first_run(&mut d), 0);
+    d.dispatch(&res);
+    assert_eq!(first_run(&mut d), 0);
+    d.reset();
+    assert_eq!(first_run(&mut d), 0);
+
+    assert_eq!(d.step(&res).unwrap().id, Some(0));
+    d.dispatch_seq(&res);
+    assert_eq!(d.step(&res).unwrap().id, Some(0));
+}
+
+#[test]
+fn dispatch_startup() {
+    struct Seed(u32);
+    struct Runs(u32);
+
+    struct Init;
+
+    impl<'a> System<'a> for Init {
+        type SystemData = (FetchMut<'a, Seed>, FetchMut<'a, Runs>);
+
+        fn run(&mut self, (mut seed, mut runs): Self::SystemData) {
+            seed.0 = 42;
+            runs.0 += 1;
+        }
+    }
+
+    struct UseSeed;
+
+    impl<'a> System<'a> for UseSeed {
+        type SystemData = (Fetch<'a, Seed>, FetchMut<'a, Runs>);
+
+        fn run(&mut self, (seed, mut runs): Self::SystemData) {
+            assert_eq!(seed.0, 42);
+            runs.0 += 10;
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Seed(0));
+    res.add(Runs(0));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with_startup(Init)
+        .with(UseSeed, "a", &[])
+        .with(UseSeed, "b", &[])
+        .with(UseSeed, "c", &[])
+        .build();
+
+    d.dispatch(&res);
+    d.dispatch(&res);
+
+    assert_eq!(res.fetch::<Runs>(0).0, 61);
+}
+
+#[test]
+fn dispatch_shutdown() {
+    struct Log(Vec<&'static str>);
+
+    struct Push(&'static str);
+
+    impl<'a> System<'a> for Push {
+        type SystemData = FetchMut<'a, Log>;
+
+        fn run(&mut self, mut log: Self::SystemData) {
+            log.0.push(self.0);
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Log(Vec::new()));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(Push("dispatch"), "dispatch", &[])
+        .with_shutdown(Push("finalize"), "finalize", &[])
+        .with_shutdown(Push("save"), "save", &["finalize"])
+        .build();
+
+    d.dispatch(&res);
+    assert_eq!(res.fetch::<Log>(0).0, vec!["dispatch"]);
+
+    d.shutdown(&res);
+    assert_eq!(res.fetch::<Log>(0).0, vec!["dispatch", "finalize", "save"]);
+}
+
+#[test]
+#[should_panic(expected = "dispatcher has been shut down")]
+fn dispatch_after_shutdown() {
+    let mut res = Resources::new();
+    res.add(Res);
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with(DummySys, "dummy", &[])
+        .build();
+
+    d.shutdown(&res);
+    d.dispatch(&res);
+}
+
+#[test]
+fn dispatch_declared() {
+    struct Log(Vec<&'static str>);
+
+    struct Push(&'static str);
+
+    impl<'a> System<'a> for Push {
+        type SystemData = FetchMut<'a, Log>;
+
+        fn run(&mut self, mut log: Self::SystemData) {
+            log.0.push(self.0);
+        }
+    }
+
+    let mut res = Resources::new();
+    res.add(Log(Vec::new()));
+
+    let mut d: Dispatcher = DispatcherBuilder::new()
+        .with_declared("a")
+        .with_declared("b")
+        .with(Push("b"), "b", &["a"])
+        .with(Push("a"), "a", &[])
+        .build();
+    d.dispatch(&res);
+
+    assert_eq!(res.fetch::<Log>(0).0, vec!["a", "b"]);
+}
+
+#[test]
+#[should_panic(expected = "No such system registered (\"a\")")]
+fn dispatch_declared_missing() {
+    DispatcherBuilder::new()
+        .with_declared("a")
+        .with(DummySys, "b", &["a"])
+        .build();
+}
+
+#[test]
+fn dispatch_builder_inspect() {
+    let mut count = 0;
+
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "b", &[])
+        .inspect(|b| count = b.dependencies().len())
+        .with(DummySys, "c", &[]);
+
+    assert_eq!(count, 2);
+    assert_eq!(builder.dependencies().len(), 3);
+}
+
+#[test]
+fn dispatch_labels() {
+    let builder = DispatcherBuilder::new()
+        .with(DummySysMut, "rigid_bodies", &[])
+        .with(DummySysMut, "collisions", &[])
+        .with(DummySysMut, "joints", &[])
+        .with(DummySys, "render", &[])
+        .with_label("rigid_bodies", "physics")
+        .with_label("collisions", "physics")
+        .with_label("joints", "physics")
+        .with_dependency_on_label("render", "physics");
+
+    assert_eq!(
+        builder.systems_with_label("physics"),
+        vec!["rigid_bodies", "collisions", "joints"]
+    );
+    assert!(builder.systems_with_label("audio").is_empty());
+
+    let deps = builder.dependencies();
+    let render_deps: Vec<_> = deps.dependencies_of(3)
+        .iter()
+        .map(|dep| deps.name(dep.0))
+        .collect();
+    assert_eq!(render_deps, vec!["rigid_bodies", "collisions", "joints"]);
+}
+
+#[test]
+fn dispatch_assert_no_conflicts() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    use shred::ResourceId;
+
+    let builder = DispatcherBuilder::new()
+        .with(DummySysMut, "a", &[])
+        .with(DummySysMut, "b", &["a"])
+        .with(DummySys, "c", &[]);
+    builder.assert_no_conflicts();
+
+    let builder = builder.with(DummySysMut, "d", &[]);
+    let err = catch_unwind(AssertUnwindSafe(|| {
+        builder.assert_no_conflicts();
+    })).unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+
+    let res = ResourceId::new::<Res>();
+    assert_eq!(
+        *message,
+        format!(
+            "Conflicting systems: \"a\" and \"d\" both write {:?}; \
+             \"b\" and \"d\" both write {:?}",
+            res, res
+        )
+    );
+}
+
+#[test]
+fn dispatch_read_set() {
+    use shred::ResourceId;
+
+    struct Clock;
+
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "b", &[])
+        .with(DummySysMut, "c", &[])
+        .with_read_set::<Clock>(&["a", "b", "c"])
+        .with_write_set::<Clock>(&["c"]);
+    let deps = builder.dependencies();
+
+    let clock = ResourceId::new::<Clock>();
+    for id in 0..2 {
+        assert!(deps.reads_of(id).contains(&clock));
+    }
+    assert!(!deps.writes_of(0).contains(&clock));
+    assert!(!deps.reads_of(2).contains(&clock));
+    assert!(deps.writes_of(2).contains(&clock));
+    assert!(deps.resources().contains(&clock));
+
+    // Reading a resource which is written already doesn't change anything.
+    let builder = builder.with_read_set::<Clock>(&["c"]);
+    let deps = builder.dependencies();
+    assert!(!deps.reads_of(2).contains(&clock));
+    assert!(deps.writes_of(2).contains(&clock));
+}
+
+#[test]
+fn dispatch_topological_levels() {
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "A", &[])
+        .with(DummySys, "B", &["A"])
+        .with(DummySys, "C", &["A"])
+        .with(DummySys, "D", &["B", "C"]);
+
+    assert_eq!(
+        builder.topological_levels(),
+        vec![vec!["A"], vec!["B", "C"], vec!["D"]]
+    );
+
+    let builder = builder.with_dependency("B", "C");
+    assert_eq!(
+        builder.topological_levels(),
+        vec![vec!["A"], vec!["B"], vec!["C"], vec!["D"]]
+    );
+}
+
+#[test]
+fn dispatch_many() {
+    let mut res = Resources::new();
+    res.add(Res);
+
+    let systems = (0..10).map(|i| {
+        let deps = if i == 0 {
+            vec![]
+        } else {
+            vec![format!("sys{}", i - 1)]
+        };
+
+        (DummySys, format!("sys{}", i), deps)
+    });
+    let builder = DispatcherBuilder::new().with_many(systems);
+
+    let deps = builder.dependencies();
+    for i in 0..10 {
+        assert_eq!(deps.name(i), format!("sys{}", i));
+    }
+    assert_eq!(deps.dependencies_of(9).len(), 1);
+
+    let mut d = builder.build();
+    assert_eq!(d.num_systems(), 10);
+    d.dispatch(&res);
+}
+
+#[test]
+fn dispatch_duration() {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    struct Sleep;
+
+    impl<'a> System<'a> for Sleep {
+        type SystemData = ();
+
+        fn run(&mut self, _: ()) {
+            sleep(Duration::from_millis(10));
+        }
+    }
+
+    let res = Resources::new();
+    let mut d: Dispatcher = DispatcherBuilder::new().with(Sleep, "sleep", &[]).build();
+    assert_eq!(d.last_dispatch_duration(), Duration::new(0, 0));
+
+    d.dispatch(&res);
+    assert!(d.last_dispatch_duration() >= Duration::from_millis(10));
+}
+
+#[test]
+fn dispatch_benchmark_graph() {
+    use std::collections::HashMap;
+
+    // A binary tree; every system depends on its two children.
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "leaf0", &[])
+        .with(DummySys, "leaf1", &[])
+        .with(DummySys, "leaf2", &[])
+        .with(DummySys, "leaf3", &[])
+        .with(DummySys, "inner0", &["leaf0", "leaf1"])
+        .with(DummySys, "inner1", &["leaf2", "leaf3"])
+        .with(DummySys, "root", &["inner0", "inner1"]);
+
+    let names = ["leaf0", "leaf1", "leaf2", "leaf3", "inner0", "inner1", "root"];
+    let costs: HashMap<_, _> = names.iter().map(|name| (name.to_string(), 100)).collect();
+    let report = builder.benchmark_graph(&costs);
+
+    assert_eq!(report.sequential_us, 700);
+    assert_eq!(report.critical_path_us, 300);
+    assert!((report.theoretical_speedup - 7.0 / 3.0).abs() < 1e-9);
+    assert_eq!(report.bottleneck_systems, vec!["leaf0", "inner0", "root"]);
+
+    let mut costs = costs;
+    costs.insert("leaf3".to_owned(), 400);
+    let report = builder.benchmark_graph(&costs);
+
+    assert_eq!(report.critical_path_us, 600);
+    assert_eq!(report.bottleneck_systems, vec!["leaf3", "inner1", "root"]);
+}
+
+#[test]
+#[should_panic(expected = "System \"both\" lists ResourceId(")]
+fn dispatch_read_and_write() {
+    use shred::ResourceId;
+
+    let id = ResourceId::new::<Res>();
+    DispatcherBuilder::new().with_from_closure(vec![id], vec![id], |_: &Resources| {}, "both", &[]);
+}
+
+#[test]
+fn dispatch_read_and_write_collect() {
+    use shred::{BuildError, ErrorStrategy, ResourceId};
+
+    let id = ResourceId::new::<Res>();
+    let errors = DispatcherBuilder::new()
+        .with_error_strategy(ErrorStrategy::Collect)
+        .with_from_closure(vec![id], vec![id], |_: &Resources| {}, "both", &[])
+        .try_build()
+        .err()
+        .unwrap();
+
+    assert_eq!(
+        errors,
+        vec![
+            BuildError::ReadAndWrite {
+                system: "both".to_owned(),
+                resource: id,
+            },
+        ]
+    );
+}
+
+#[test]
+fn dispatch_count_systems() {
+    let mut builder = DispatcherBuilder::new();
+    assert_eq!((builder.count_systems(), builder.count_ready_systems()), (0, 0));
+
+    builder.add(DummySys, "a", &[]);
+    assert_eq!((builder.count_systems(), builder.count_ready_systems()), (1, 1));
+
+    builder.add(DummySys, "b", &["a"]);
+    assert_eq!((builder.count_systems(), builder.count_ready_systems()), (2, 1));
+
+    builder.add(DummySys, "c", &["b"]);
+    builder.add_thread_local(DummySys);
+    assert_eq!((builder.count_systems(), builder.count_ready_systems()), (3, 1));
+}
+
+#[test]
+fn dispatch_after_label() {
+    let mut builder = DispatcherBuilder::new().with(DummySys, "input", &[]);
+    for i in 0..5 {
+        let name = format!("physics{}", i);
+        builder.add(DummySysMut, &name, &["input"]);
+        builder.add_label(&name, "physics");
+    }
+    builder.add_after_label(DummySys, "post_physics", "physics", &["input"]);
+
+    let deps = builder.dependencies();
+    let post_deps: Vec<_> = deps.dependencies_of(6)
+        .iter()
+        .map(|dep| deps.name(dep.0))
+        .collect();
+    assert_eq!(
+        post_deps,
+        vec!["input", "physics0", "physics1", "physics2", "physics3", "physics4"]
+    );
+}
+
+#[test]
+fn dispatch_max_concurrent() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let mut builder = DispatcherBuilder::new();
+    for _ in 0..8 {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        builder.add_from_closure(
+            vec![],
+            vec![],
+            move |_: &Resources| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                sleep_short();
+                running.fetch_sub(1, Ordering::SeqCst);
+            },
+            "",
+            &[],
+        );
+    }
+
+    let res = Resources::new();
+    let mut d = builder.build();
+    d.set_max_concurrent(3);
+    d.dispatch(&res);
+
+    let max_running = max_running.load(Ordering::SeqCst);
+    assert!((1..=3).contains(&max_running));
+}
+
+#[test]
+fn dispatch_from_iterator() {
+    use std::sync::{Arc, Mutex};
+
+    use shred::RegisteredSystem;
+
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let system = |name: &str, deps: &[&str]| {
+        let order = order.clone();
+        let name = name.to_owned();
+
+        RegisteredSystem {
+            name: name.clone(),
+            reads: vec![],
+            writes: vec![],
+            deps: deps.iter().map(|dep| dep.to_string()).collect(),
+            run: Box::new(move |_: &Resources| order.lock().unwrap().push(name.clone())),
+        }
+    };
+
+    // Listed in reverse dependency order
+    let systems = vec![system("c", &["b"]), system("b", &["a"]), system("a", &[])];
+    let mut d: Dispatcher = systems.into_iter().collect::<DispatcherBuilder>().build();
+
+    d.dispatch(&Resources::new());
+    assert_eq!(*order.lock().unwrap(), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn dispatch_dependencies() {
+    let d: Dispatcher = DispatcherBuilder::new()
+        .with_declared("c")
+        .with(DummySys, "a", &[])
+        .with(DummySys, "b", &["a", "c"])
+        .with(DummySysMut, "c", &[])
+        .with_thread_local(DummySys)
+        .build();
+    let deps = d.dependencies();
+
+    assert_eq!(deps.len(), 3);
+    assert_eq!(deps.edges().count(), 2);
+    assert!(deps.edges().any(|edge| edge == (1, 2)));
+    assert_eq!(deps.name(2), "c");
+}
+
+#[test]
+fn dispatch_remove_label() {
+    let builder = DispatcherBuilder::new()
+        .with(DummySysMut, "rigid_bodies", &[])
+        .with(DummySysMut, "collisions", &[])
+        .with(DummySys, "render", &[])
+        .with_label("rigid_bodies", "physics")
+        .with_label("collisions", "physics")
+        .without_label("collisions", "physics")
+        .without_label("collisions", "physics")
+        .without_label("render", "audio");
+    assert_eq!(builder.systems_with_label("physics"), vec!["rigid_bodies"]);
+
+    let builder = builder
+        .without_label("rigid_bodies", "physics")
+        .with_dependency_on_label("render", "physics");
+    assert!(builder.systems_with_label("physics").is_empty());
+
+    let d: Dispatcher = builder.build();
+    assert!(d.dependencies().dependencies_of(2).is_empty());
+}
+
+#[test]
+fn dispatch_summary() {
+    let builder = DispatcherBuilder::new()
+        .with(DummySys, "a", &[])
+        .with(DummySys, "b", &["a"])
+        .with(DummySys, "c", &["a"])
+        .with(DummySys, "d", &["a"])
+        .with(DummySysMut, "e", &["b", "c"])
+        .with(DummySys, "f", &[])
+        .with_write_set::<ResB>(&["f"])
+        .with_read_set::<u32>(&["f"]);
+
+    assert_eq!(
+        builder.summary(),
+        "Systems: 6 (2 ready, 4 dependent), \
+         Resources: 2 read-write / 1 read-only, \
+         Critical path length: 3 systems, \
+         Max theoretical parallelism: 3"
+    );
+    builder.print_stats();
+}
+
+#[test]
+fn dispatch_panic_payload() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    struct Panic;
+
+    impl<'a> System<'a> for Panic {
+        type SystemData = ();
+
+        fn run(&mut self, _: Self::SystemData) {
+            panic::resume_unwind(Box::new(42i32));
+        }
+    }
+
+    let mut d: Dispatcher = DispatcherBuilder::new().with(Panic, "p", &[]).build();
+    let res = Resources::new();
+
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| d.dispatch(&res))).unwrap_err();
+    assert_eq!(payload.downcast_ref::<i32>(), Some(&42));
+}
+
 #[test]
 fn dispatch_stage_group() {
     let mut res = Resources::new();
@@ -0,0 +1,188 @@
+#![cfg(feature = "log")]
+
+extern crate log;
+extern crate shred;
+
+use std::cell::RefCell;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use shred::{DispatcherBuilder, Fetch, FetchMut, ResourceId, Resources, System};
+
+thread_local! {
+    static RECORDS: RefCell<Vec<(Level, String)>> = RefCell::new(Vec::new());
+}
+
+struct TestLogger;
+
+impl Log for TestLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let entry = (record.level(), record.args().to_string());
+        RECORDS.with(|r| r.borrow_mut().push(entry));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: TestLogger = TestLogger;
+
+/// Installs the logger and returns the messages
+/// logged on this thread while running `f`.
+fn records<F: FnOnce()>(f: F) -> Vec<(Level, String)> {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(LevelFilter::Trace);
+
+    RECORDS.with(|r| r.borrow_mut().clear());
+    f();
+
+    RECORDS.with(|r| r.borrow_mut().drain(..).collect())
+}
+
+/// Like `records`, but only returns the warnings.
+fn warnings<F: FnOnce()>(f: F) -> Vec<String> {
+    records(f)
+        .into_iter()
+        .filter(|(level, _)| *level == Level::Warn)
+        .map(|(_, message)| message)
+        .collect()
+}
+
+struct Res;
+
+struct DummySys;
+
+impl<'a> System<'a> for DummySys {
+    type SystemData = Fetch<'a, Res>;
+
+    fn run(&mut self, _: Self::SystemData) {}
+}
+
+struct DummySysMut;
+
+impl<'a> System<'a> for DummySysMut {
+    type SystemData = FetchMut<'a, Res>;
+
+    fn run(&mut self, _: Self::SystemData) {}
+}
+
+#[test]
+fn warn_orphan() {
+    let messages = warnings(|| {
+        DispatcherBuilder::new()
+            .with(DummySys, "a", &[])
+            .with(DummySys, "b", &["a"])
+            .with(DummySys, "c", &["a"])
+            .with(DummySys, "d", &["b"])
+            .with_terminal("d")
+            .build();
+    });
+
+    assert_eq!(
+        messages,
+        vec!["System \"c\" has no dependents and may be an orphan".to_owned()]
+    );
+}
+
+#[test]
+fn no_warning_for_terminal() {
+    let messages = warnings(|| {
+        DispatcherBuilder::new()
+            .with(DummySys, "a", &[])
+            .with(DummySys, "b", &["a"])
+            .with_terminal("b")
+            .build();
+    });
+
+    assert!(messages.is_empty());
+}
+
+#[test]
+fn warn_rewrite() {
+    let messages = warnings(|| {
+        DispatcherBuilder::new()
+            .with(DummySysMut, "a", &[])
+            .with(DummySysMut, "b", &["a"])
+            .with_terminal("b")
+            .build();
+    });
+
+    assert_eq!(
+        messages,
+        vec![
+            format!(
+                "System \"b\" writes resource {:?} which was already \
+                 written by its dependency \"a\"",
+                ResourceId::new::<Res>()
+            ),
+        ]
+    );
+
+    let messages = warnings(|| {
+        DispatcherBuilder::new()
+            .with(DummySysMut, "a", &[])
+            .with(DummySysMut, "b", &["a"])
+            .with_terminal("b")
+            .with_allowed_rewrite("b", ResourceId::new::<Res>())
+            .build();
+    });
+
+    assert!(messages.is_empty());
+}
+
+#[test]
+fn warn_no_resources() {
+    struct Timer;
+
+    impl<'a> System<'a> for Timer {
+        type SystemData = ();
+
+        fn run(&mut self, _: ()) {}
+    }
+
+    let messages = warnings(|| {
+        DispatcherBuilder::new()
+            .with(DummySys, "a", &[])
+            .with(Timer, "timer", &[])
+            .build();
+    });
+
+    assert_eq!(
+        messages,
+        vec![
+            "System \"timer\" declares no resource access; \
+             did you forget its `SystemData`?"
+                .to_owned(),
+        ]
+    );
+
+    let messages = warnings(|| {
+        DispatcherBuilder::new()
+            .with(Timer, "timer", &[])
+            .with_allowed_no_resources("timer")
+            .build();
+    });
+
+    assert!(messages.is_empty());
+}
+
+#[test]
+fn resource_messages() {
+    let messages = records(|| {
+        let mut res = Resources::new();
+        res.add(5u8);
+        res.fetch::<u8>(0);
+        res.fetch_mut::<u8>(0);
+    });
+
+    assert_eq!(
+        messages,
+        vec![
+            (Level::Debug, "Resources: add \"u8\"".to_owned()),
+            (Level::Trace, "Resources: fetch \"u8\" (read)".to_owned()),
+            (Level::Trace, "Resources: fetch \"u8\" (write)".to_owned()),
+        ]
+    );
+}